@@ -0,0 +1,139 @@
+#[macro_use]
+extern crate criterion;
+extern crate http;
+extern crate router;
+
+use criterion::Criterion;
+use http::Method;
+use router::Router;
+
+const STATIC_ROUTES: &[&str] = &[
+    "/",
+    "/about",
+    "/blog",
+    "/blog/archive",
+    "/contact",
+    "/docs",
+    "/docs/getting-started",
+    "/docs/installation",
+    "/docs/configuration",
+    "/docs/routing",
+    "/docs/middleware",
+    "/docs/deployment",
+    "/docs/faq",
+    "/api/v1/health",
+    "/api/v1/status",
+    "/api/v1/users",
+    "/api/v1/users/me",
+    "/api/v1/users/me/settings",
+    "/api/v1/organizations",
+    "/api/v1/projects",
+    "/api/v1/projects/recent",
+    "/api/v1/notifications",
+    "/api/v1/notifications/unread",
+    "/api/v2/users",
+    "/api/v2/projects",
+    "/login",
+    "/logout",
+    "/pricing",
+    "/privacy",
+    "/register",
+    "/search",
+    "/settings",
+    "/settings/account",
+    "/settings/billing",
+    "/settings/notifications",
+    "/settings/security",
+    "/static/app.css",
+    "/static/app.js",
+    "/static/favicon.ico",
+    "/terms",
+];
+
+const STATIC_PATHS: &[&str] = &[
+    "/",
+    "/docs/routing",
+    "/api/v1/users/me/settings",
+    "/api/v1/notifications/unread",
+    "/settings/security",
+    "/static/favicon.ico",
+    "/terms",
+    "/not/found",
+];
+
+const PARAM_ROUTES: &[&str] = &[
+    "/users/:user",
+    "/users/:user/repos",
+    "/users/:user/followers",
+    "/users/:user/following/:target",
+    "/orgs/:org",
+    "/orgs/:org/members/:user",
+    "/orgs/:org/teams",
+    "/repos/:owner/:repo",
+    "/repos/:owner/:repo/branches/:branch",
+    "/repos/:owner/:repo/commits/:sha",
+    "/repos/:owner/:repo/contents/*path",
+    "/repos/:owner/:repo/issues",
+    "/repos/:owner/:repo/issues/:number<int>",
+    "/repos/:owner/:repo/issues/:number<int>/comments",
+    "/repos/:owner/:repo/issues/:number<int>/comments/:id<int>",
+    "/repos/:owner/:repo/pulls/:number<int>",
+    "/repos/:owner/:repo/pulls/:number<int>/files",
+    "/repos/:owner/:repo/releases/:id<int>/assets",
+    "/repos/:owner/:repo/releases/tags/:tag",
+    "/gists/:id",
+    "/gists/:id/comments/:comment",
+    "/packages/:name@:version",
+];
+
+const PARAM_PATHS: &[&str] = &[
+    "/users/rkusa",
+    "/users/rkusa/following/octocat",
+    "/orgs/rust-lang/members/rkusa",
+    "/repos/rkusa/router-rs/commits/2f1e4a7",
+    "/repos/rkusa/router-rs/contents/router/src/lib.rs",
+    "/repos/rkusa/router-rs/issues/42/comments/1337",
+    "/repos/rkusa/router-rs/releases/tags/v0.1.0",
+    "/packages/router@0.1.0",
+    "/repos/rkusa/router-rs/issues/foo",
+];
+
+fn router(routes: &[&str]) -> Router<usize> {
+    let mut router = Router::default();
+    for (i, route) in routes.iter().enumerate() {
+        router.get(route, i);
+    }
+    router
+}
+
+fn bench(c: &mut Criterion, name: &str, routes: &[&str], paths: &'static [&'static str]) {
+    let tree = router(routes);
+    c.bench_function(&format!("{}/tree", name), move |b| {
+        b.iter(|| {
+            for path in paths {
+                criterion::black_box(tree.resolve(&Method::GET, path));
+            }
+        })
+    });
+
+    let mut frozen = router(routes);
+    frozen.freeze();
+    c.bench_function(&format!("{}/frozen", name), move |b| {
+        b.iter(|| {
+            for path in paths {
+                criterion::black_box(frozen.resolve(&Method::GET, path));
+            }
+        })
+    });
+}
+
+fn static_routes(c: &mut Criterion) {
+    bench(c, "static", STATIC_ROUTES, STATIC_PATHS);
+}
+
+fn param_routes(c: &mut Criterion) {
+    bench(c, "params", PARAM_ROUTES, PARAM_PATHS);
+}
+
+criterion_group!(benches, static_routes, param_routes);
+criterion_main!(benches);
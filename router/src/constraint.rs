@@ -0,0 +1,126 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+/// A constraint a param's (percent-decoded) value has to satisfy, e.g. `int` in `:id<int>` or
+/// `re:[a-z]+` in `:slug<re:[a-z]+>`.
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    /// One or more ASCII digits.
+    Int,
+    /// A hyphenated UUID, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    Uuid,
+    /// One or more alphabetic characters.
+    Alpha,
+    /// A custom regular expression (prefixed with `re:`) that has to match the whole value.
+    #[cfg(feature = "regex")]
+    Regex { source: String, regex: Regex },
+}
+
+impl Constraint {
+    pub fn parse(source: &str) -> Result<Self, &'static str> {
+        match source {
+            "int" => Ok(Constraint::Int),
+            "uuid" => Ok(Constraint::Uuid),
+            "alpha" => Ok(Constraint::Alpha),
+            #[cfg(feature = "regex")]
+            _ if source.starts_with("re:") => Regex::new(&format!("^(?:{})$", &source[3..]))
+                .map(|regex| Constraint::Regex {
+                    source: source.to_string(),
+                    regex,
+                })
+                .map_err(|_| "invalid regex constraint"),
+            #[cfg(not(feature = "regex"))]
+            _ if source.starts_with("re:") => {
+                Err("regex parameter constraints require the `regex` feature")
+            }
+            _ => Err("unknown parameter constraint"),
+        }
+    }
+
+    /// The constraint as it appeared in the pattern (without the angle brackets).
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Constraint::Int => "int",
+            Constraint::Uuid => "uuid",
+            Constraint::Alpha => "alpha",
+            #[cfg(feature = "regex")]
+            Constraint::Regex { ref source, .. } => source,
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match *self {
+            Constraint::Int => !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()),
+            Constraint::Uuid => {
+                value.len() == 36
+                    && value.bytes().enumerate().all(|(i, b)| match i {
+                        8 | 13 | 18 | 23 => b == b'-',
+                        _ => b.is_ascii_hexdigit(),
+                    })
+            }
+            Constraint::Alpha => !value.is_empty() && value.chars().all(char::is_alphabetic),
+            #[cfg(feature = "regex")]
+            Constraint::Regex { ref regex, .. } => regex.is_match(value),
+        }
+    }
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Constraint;
+
+    #[test]
+    fn builtin() {
+        let int = Constraint::parse("int").unwrap();
+        assert!(int.matches("42"));
+        assert!(!int.matches(""));
+        assert!(!int.matches("-1"));
+        assert!(!int.matches("4a"));
+
+        let uuid = Constraint::parse("uuid").unwrap();
+        assert!(uuid.matches("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(uuid.matches("67E55044-10B1-426F-9247-BB680E5FE0C8"));
+        assert!(!uuid.matches("67e55044-10b1-426f-9247-bb680e5fe0c"));
+        assert!(!uuid.matches("67e55044x10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!uuid.matches("67e55044-10b1-426f-9247-bb680e5fe0cg"));
+
+        let alpha = Constraint::parse("alpha").unwrap();
+        assert!(alpha.matches("foo"));
+        assert!(alpha.matches("café"));
+        assert!(!alpha.matches(""));
+        assert!(!alpha.matches("foo1"));
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            Constraint::parse("itn"),
+            Err("unknown parameter constraint")
+        );
+        assert!(Constraint::parse("[a-z]+").is_err());
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn regex_requires_feature() {
+        assert!(Constraint::parse("re:[a-z]+").is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex() {
+        let slug = Constraint::parse("re:[a-z0-9]+(?:-[a-z0-9]+)*").unwrap();
+        assert_eq!(slug.as_str(), "re:[a-z0-9]+(?:-[a-z0-9]+)*");
+        assert!(slug.matches("hello-world"));
+        assert!(!slug.matches("hello--world"));
+        assert!(!slug.matches("Hello"));
+        assert!(Constraint::parse("re:[a-z").is_err());
+        assert!(Constraint::parse("[a-z]+").is_err());
+    }
+}
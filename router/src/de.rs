@@ -0,0 +1,215 @@
+use error::ParamError;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use std::fmt;
+
+impl de::Error for ParamError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ParamError::Deserialize {
+            message: msg.to_string(),
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        ParamError::Missing {
+            name: field.to_string(),
+        }
+    }
+}
+
+/// Deserializes params as a map from their names to their values.
+pub struct ParamsDeserializer<'de, I> {
+    iter: I,
+    current: Option<(&'de str, &'de str)>,
+}
+
+impl<'de, I> ParamsDeserializer<'de, I>
+where
+    I: Iterator<Item = (&'de str, &'de str)>,
+{
+    pub fn new(iter: I) -> Self {
+        ParamsDeserializer {
+            iter,
+            current: None,
+        }
+    }
+}
+
+impl<'de, I> de::Deserializer<'de> for ParamsDeserializer<'de, I>
+where
+    I: Iterator<Item = (&'de str, &'de str)>,
+{
+    type Error = ParamError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParamError> {
+        visitor.visit_map(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, I> MapAccess<'de> for ParamsDeserializer<'de, I>
+where
+    I: Iterator<Item = (&'de str, &'de str)>,
+{
+    type Error = ParamError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, ParamError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((name, value)) => {
+                self.current = Some((name, value));
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, ParamError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (name, value) = self
+            .current
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer { name, value })
+    }
+}
+
+/// Deserializes a single param value, parsing it via `FromStr` if a primitive is requested.
+struct ValueDeserializer<'de> {
+    name: &'de str,
+    value: &'de str,
+}
+
+macro_rules! parse {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParamError> {
+                match self.value.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(err) => Err(ParamError::Invalid {
+                        name: self.name.to_string(),
+                        value: self.value.to_string(),
+                        message: err.to_string(),
+                    }),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = ParamError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParamError> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    parse! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParamError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ParamError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ParamError> {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {ParamError, Params};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Post<'a> {
+        user: &'a str,
+        id: u64,
+        draft: Option<bool>,
+        format: Format,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Format {
+        Html,
+        Json,
+    }
+
+    #[test]
+    fn deserialize() {
+        let mut params = Params::new();
+        params.push("user", "rkusa");
+        params.push("id", "42");
+        params.push("format", "json");
+        assert_eq!(
+            params.deserialize::<Post>(),
+            Ok(Post {
+                user: "rkusa",
+                id: 42,
+                draft: None,
+                format: Format::Json,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let mut params = Params::new();
+        params.push("user", "rkusa");
+        params.push("id", "foo");
+        params.push("format", "json");
+        assert_eq!(
+            params.deserialize::<Post>(),
+            Err(ParamError::Invalid {
+                name: "id".to_string(),
+                value: "foo".to_string(),
+                message: "invalid digit found in string".to_string(),
+            })
+        );
+
+        params.truncate(1);
+        assert_eq!(
+            params.into_owned().deserialize::<Post>(),
+            Err(ParamError::Missing {
+                name: "id".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use http::Method;
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "loader")]
+use std::io;
+
+#[derive(Debug, PartialEq)]
+pub enum RouteError {
+    /// Two routes use a different name for a parameter at the same position, e.g. `/users/:id`
+    /// and `/users/:name/posts`.
+    ConflictingParamNames {
+        pattern: String,
+        existing: String,
+        name: String,
+    },
+    /// The same pattern has already been registered for the method.
+    DuplicateRoute { pattern: String },
+    /// Another route has already been registered with the same name.
+    DuplicateName { name: String },
+    /// The pattern could not be parsed, e.g. because a parameter has an empty name.
+    InvalidPattern {
+        pattern: String,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RouteError::ConflictingParamNames {
+                ref pattern,
+                ref existing,
+                ref name,
+            } => write!(
+                f,
+                "cannot have different parameter names at the same position \
+                 (`{}` conflicts with existing `{}`) in `{}`",
+                name, existing, pattern
+            ),
+            RouteError::DuplicateRoute { ref pattern } => {
+                write!(f, "route `{}` is already registered", pattern)
+            }
+            RouteError::DuplicateName { ref name } => {
+                write!(f, "a route named `{}` is already registered", name)
+            }
+            RouteError::InvalidPattern {
+                ref pattern,
+                reason,
+            } => write!(f, "invalid route pattern `{}`: {}", pattern, reason),
+        }
+    }
+}
+
+impl Error for RouteError {}
+
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    /// No route matches the path for any method.
+    NotFound,
+    /// The path matches, but only for other methods (sorted by name).
+    MethodNotAllowed { allowed: Vec<Method> },
+    /// The path only matches with a trailing slash added or removed, `location` is the path
+    /// that has been registered.
+    Redirect { location: String },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResolveError::NotFound => write!(f, "no route found"),
+            ResolveError::MethodNotAllowed { ref allowed } => {
+                write!(f, "method not allowed, allowed methods: ")?;
+                for (i, method) in allowed.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", method)?;
+                }
+                Ok(())
+            }
+            ResolveError::Redirect { ref location } => write!(f, "redirect to `{}`", location),
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+#[derive(Debug, PartialEq)]
+pub enum UrlError {
+    /// No route has been registered with the given name.
+    UnknownRoute { name: String },
+    /// The route's pattern contains a param that has not been provided.
+    MissingParam { name: String },
+    /// A param has been provided that is not part of the route's pattern.
+    ExtraParam { name: String },
+    /// A param has been provided more than once.
+    DuplicateParam { name: String },
+    /// A param's value is empty (which only catch-alls may be) or does not satisfy the param's
+    /// constraint, so the built path would not match the route.
+    InvalidParam { name: String, value: String },
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UrlError::UnknownRoute { ref name } => write!(f, "no route named `{}`", name),
+            UrlError::MissingParam { ref name } => write!(f, "missing param `{}`", name),
+            UrlError::ExtraParam { ref name } => write!(f, "unknown param `{}`", name),
+            UrlError::DuplicateParam { ref name } => write!(f, "duplicate param `{}`", name),
+            UrlError::InvalidParam {
+                ref name,
+                ref value,
+            } => write!(f, "invalid value `{}` for param `{}`", value, name),
+        }
+    }
+}
+
+impl Error for UrlError {}
+
+#[derive(Debug, PartialEq)]
+pub enum ParamError {
+    /// The param is not part of the resolved route.
+    Missing { name: String },
+    /// The param's value could not be parsed into the requested type.
+    Invalid {
+        name: String,
+        value: String,
+        message: String,
+    },
+    /// The params could not be deserialized, e.g. because of an unknown field.
+    Deserialize { message: String },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamError::Missing { ref name } => write!(f, "missing param `{}`", name),
+            ParamError::Invalid {
+                ref name,
+                ref value,
+                ref message,
+            } => write!(
+                f,
+                "invalid value `{}` for param `{}`: {}",
+                value, name, message
+            ),
+            ParamError::Deserialize { ref message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+/// Errors of loading routes from a route file (see `Router::load_routes`), with the file and (if
+/// known) the line they occurred at.
+#[cfg(feature = "loader")]
+#[derive(Debug)]
+pub enum LoadError {
+    /// The route file could not be read.
+    Io { file: String, error: io::Error },
+    /// The route file is not valid TOML/JSON or a route is malformed, e.g. because of a missing
+    /// field.
+    Parse {
+        file: String,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A route's method is not a valid HTTP method.
+    InvalidMethod {
+        file: String,
+        line: usize,
+        method: String,
+    },
+    /// A route's handler key is unknown to the registry.
+    UnknownHandler {
+        file: String,
+        line: usize,
+        key: String,
+    },
+    /// A route could not be added, e.g. because of an invalid pattern or a conflict with another
+    /// route.
+    Route {
+        file: String,
+        line: usize,
+        error: RouteError,
+    },
+}
+
+#[cfg(feature = "loader")]
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io {
+                ref file,
+                ref error,
+            } => write!(f, "{}: {}", file, error),
+            LoadError::Parse {
+                ref file,
+                line: Some(line),
+                ref message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+            LoadError::Parse {
+                ref file,
+                line: None,
+                ref message,
+            } => write!(f, "{}: {}", file, message),
+            LoadError::InvalidMethod {
+                ref file,
+                line,
+                ref method,
+            } => write!(f, "{}:{}: invalid method `{}`", file, line, method),
+            LoadError::UnknownHandler {
+                ref file,
+                line,
+                ref key,
+            } => write!(f, "{}:{}: unknown handler `{}`", file, line, key),
+            LoadError::Route {
+                ref file,
+                line,
+                ref error,
+            } => write!(f, "{}:{}: {}", file, line, error),
+        }
+    }
+}
+
+#[cfg(feature = "loader")]
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io { ref error, .. } => Some(error),
+            LoadError::Route { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
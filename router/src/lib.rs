@@ -1,36 +1,276 @@
 extern crate http;
+#[cfg(test)]
+extern crate quickcheck;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(any(feature = "loader", all(test, feature = "serde")))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "loader")]
+extern crate serde_json;
+#[cfg(feature = "loader")]
+extern crate toml;
 
 use http::Method;
 use std::collections::HashMap;
+use std::mem;
 
+mod constraint;
+#[cfg(feature = "serde")]
+mod de;
+mod error;
+#[cfg(feature = "loader")]
+mod loader;
+mod params;
 mod tree;
-pub use tree::Params;
+#[cfg(feature = "loader")]
+pub use error::LoadError;
+pub use error::{ParamError, ResolveError, RouteError, UrlError};
+#[cfg(feature = "loader")]
+pub use loader::{Registry, RouteDef, RouteFormat};
+pub use params::{OwnedParams, Params};
 use tree::Tree;
 
-pub struct Router<'a, T> {
-    routes: HashMap<Method, Tree<'a, T>>,
-    // TODO:
-    // - HEAD can execute GET
-    // - Trailing slash handling
+pub struct Router<T> {
+    routes: HashMap<Method, Tree<T>>,
+    /// Routes that match requests of any method, see `any`.
+    any: Tree<T>,
+    names: HashMap<String, String>,
+    trailing_slash: TrailingSlash,
+    case_insensitive: bool,
+}
+
+/// Defines how paths that only differ in a trailing slash (e.g. `/foo` and `/foo/`) are treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TrailingSlash {
+    /// `/foo` and `/foo/` are different paths (the default).
+    #[default]
+    Strict,
+    /// If a path does not match, it is retried with the trailing slash added or removed.
+    Ignore,
+    /// Like `Ignore`, but instead of resolving the route, a redirect to the registered path is
+    /// reported (see `ResolveError::Redirect`).
+    Redirect,
 }
 
 macro_rules! method {
     ( $name:ident, $method:expr ) => {
-        pub fn $name(&mut self, path: &'a str, handler: T)
-        {
+        pub fn $name(&mut self, path: &str, handler: T) {
             self.route($method, path, handler);
         }
     };
 }
 
-impl<'a, T> Router<'a, T> {
-    pub fn route(&mut self, method: Method, path: &'a str, handler: T) {
-        if !self.routes.contains_key(&method) {
-            let tree = Tree::new();
-            self.routes.insert(method.clone(), tree);
+impl<T> Router<T> {
+    pub fn route(&mut self, method: Method, path: &str, handler: T) {
+        if let Err(err) = self.try_route(method, path, handler) {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn try_route(&mut self, method: Method, path: &str, handler: T) -> Result<(), RouteError> {
+        let case_insensitive = self.case_insensitive;
+        self.routes
+            .entry(method)
+            .or_insert_with(|| {
+                let mut tree = Tree::new();
+                tree.set_ignore_case(case_insensitive);
+                tree
+            })
+            .try_add_path(path, handler)
+    }
+
+    /// Registers a route for all methods. Routes registered for a specific method take precedence,
+    /// i.e. an `any` route is only used if no route for the request's method matches the path
+    /// (even if the `any` route is more specific).
+    pub fn any(&mut self, path: &str, handler: T) {
+        if let Err(err) = self.try_any(path, handler) {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn try_any(&mut self, path: &str, handler: T) -> Result<(), RouteError> {
+        self.any.try_add_path(path, handler)
+    }
+
+    /// Removes the route registered for `method` with exactly the given pattern and returns its
+    /// handler.
+    pub fn remove(&mut self, method: &Method, path: &str) -> Option<T> {
+        let handler = self.routes.get_mut(method)?.remove(path)?;
+        self.remove_unused_names(path);
+        Some(handler)
+    }
+
+    /// Removes the route registered with `any` with exactly the given pattern and returns its
+    /// handler.
+    pub fn remove_any(&mut self, path: &str) -> Option<T> {
+        let handler = self.any.remove(path)?;
+        self.remove_unused_names(path);
+        Some(handler)
+    }
+
+    fn remove_unused_names(&mut self, path: &str) {
+        if !self.any.contains(path) && !self.routes.values().any(|tree| tree.contains(path)) {
+            self.names.retain(|_, pattern| pattern != path);
+        }
+    }
+
+    /// Replaces the handler of the route registered for `method` with exactly the given pattern
+    /// and returns the previous one. If there is no such route yet, it is added.
+    pub fn replace(&mut self, method: Method, path: &str, handler: T) -> Option<T> {
+        match self.try_replace(method, path, handler) {
+            Ok(previous) => previous,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_replace(
+        &mut self,
+        method: Method,
+        path: &str,
+        handler: T,
+    ) -> Result<Option<T>, RouteError> {
+        if let Some(current) = self
+            .routes
+            .get_mut(&method)
+            .and_then(|tree| tree.get_mut(path))
+        {
+            return Ok(Some(mem::replace(current, handler)));
+        }
+        self.try_route(method, path, handler).map(|_| None)
+    }
+
+    /// Registers a route like `route`, and names it so that its path can be built with `url_for`.
+    pub fn route_named(&mut self, name: &str, method: Method, path: &str, handler: T) {
+        if let Err(err) = self.try_route_named(name, method, path, handler) {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn try_route_named(
+        &mut self,
+        name: &str,
+        method: Method,
+        path: &str,
+        handler: T,
+    ) -> Result<(), RouteError> {
+        if self.names.contains_key(name) {
+            return Err(RouteError::DuplicateName {
+                name: name.to_string(),
+            });
+        }
+        self.try_route(method, path, handler)?;
+        self.names.insert(name.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Builds the path of the route with the given `name` by replacing all params of its
+    /// pattern with the percent-encoded values of `params`.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let pattern = self.names.get(name).ok_or_else(|| UrlError::UnknownRoute {
+            name: name.to_string(),
+        })?;
+        tree::build_path(pattern, params)
+    }
+
+    /// Merges all routes of `other` into this router, with `prefix` (which can contain params)
+    /// prepended to their patterns. A root route `/` of `other` is registered as the bare
+    /// `prefix` (e.g. `/api` instead of `/api/`). The trailing slash policy of `other` is not
+    /// taken over.
+    pub fn mount(&mut self, prefix: &str, other: Router<T>) {
+        if let Err(err) = self.try_mount(prefix, other) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `mount`, but returns an error instead of panicking if a route of `other` conflicts
+    /// with an existing one. The router is left unchanged in that case.
+    pub fn try_mount(&mut self, prefix: &str, other: Router<T>) -> Result<(), RouteError> {
+        let prefix = prefix.trim_end_matches('/');
+        if let Some(name) = other
+            .names
+            .keys()
+            .find(|name| self.names.contains_key(*name))
+        {
+            return Err(RouteError::DuplicateName { name: name.clone() });
+        }
+
+        let routes = other
+            .routes
+            .into_iter()
+            .flat_map(|(method, tree)| {
+                tree.into_routes()
+                    .into_iter()
+                    .map(move |(pattern, handler)| (Some(method.clone()), pattern, handler))
+            })
+            .chain(
+                other
+                    .any
+                    .into_routes()
+                    .into_iter()
+                    .map(|(pattern, handler)| (None, pattern, handler)),
+            );
+        // on a conflict, the routes added so far are removed again and the trees that have been
+        // frozen before (which adding a route undoes) are frozen again
+        let methods = self.routes.keys().cloned().collect::<Vec<_>>();
+        let mut added: Vec<(Option<Method>, String, bool)> = Vec::new();
+        for (method, pattern, handler) in routes {
+            let pattern = prefixed(prefix, &pattern);
+            let was_frozen = match method {
+                Some(ref method) => self.routes.get(method).is_some_and(Tree::is_frozen),
+                None => self.any.is_frozen(),
+            };
+            let result = match method {
+                Some(ref method) => self.try_route(method.clone(), &pattern, handler),
+                None => self.try_any(&pattern, handler),
+            };
+            if let Err(err) = result {
+                for (method, pattern, was_frozen) in added.into_iter().rev() {
+                    let tree = match method {
+                        Some(method) => self.routes.get_mut(&method).expect("route has been added"),
+                        None => &mut self.any,
+                    };
+                    tree.remove(&pattern);
+                    if was_frozen {
+                        tree.freeze();
+                    }
+                }
+                self.routes.retain(|method, _| methods.contains(method));
+                return Err(err);
+            }
+            added.push((method, pattern, was_frozen));
+        }
+        for (name, pattern) in other.names {
+            self.names.insert(name, prefixed(prefix, &pattern));
         }
-        let tree = self.routes.get_mut(&method).unwrap();
-        tree.add_path(path, handler);
+
+        Ok(())
+    }
+
+    /// Returns all registered routes as `(method, pattern, handler)`, sorted by pattern and
+    /// method. Patterns are returned as they have been registered (including mount prefixes).
+    /// Routes registered with `any` are not included, see `any_routes`.
+    pub fn routes(&self) -> impl Iterator<Item = (&Method, &str, &T)> {
+        let mut routes = self
+            .routes
+            .iter()
+            .flat_map(|(method, tree)| {
+                tree.routes()
+                    .map(move |(pattern, handler)| (method, pattern, handler))
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by(|lhs, rhs| (lhs.1, lhs.0.as_str()).cmp(&(rhs.1, rhs.0.as_str())));
+        routes.into_iter()
+    }
+
+    /// Returns all routes registered with `any` as `(pattern, handler)`, sorted by pattern.
+    pub fn any_routes(&self) -> impl Iterator<Item = (&str, &T)> {
+        let mut routes = self.any.routes().collect::<Vec<_>>();
+        routes.sort_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+        routes.into_iter()
     }
 
     method!(options, Method::OPTIONS);
@@ -40,18 +280,849 @@ impl<'a, T> Router<'a, T> {
     method!(delete, Method::DELETE);
     method!(head, Method::HEAD);
     method!(patch, Method::PATCH);
+    method!(trace, Method::TRACE);
+    method!(connect, Method::CONNECT);
+
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    /// Matches the static parts of all routes case-insensitively (e.g. `/Users/:name` matches
+    /// `/users/John`), while params keep the casing of the resolved path. While enabled, adding a
+    /// route that only differs in case from an existing one fails with `DuplicateRoute`, so it
+    /// should be enabled before routes are added.
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.case_insensitive = enabled;
+        for tree in self.routes.values_mut() {
+            tree.set_ignore_case(enabled);
+        }
+        self.any.set_ignore_case(enabled);
+    }
+
+    /// Flattens the routes into a structure that is faster to resolve. Routes added or removed
+    /// afterwards still work, but are resolved more slowly until the router is frozen again.
+    pub fn freeze(&mut self) {
+        for tree in self.routes.values_mut() {
+            tree.freeze();
+        }
+        self.any.freeze();
+    }
+
+    pub fn resolve<'r, 'p>(
+        &'r self,
+        method: &Method,
+        path: &'p str,
+    ) -> Option<(&'r T, Params<'r, 'p>)> {
+        self.find(method, path, false).or_else(|| {
+            if self.trailing_slash != TrailingSlash::Ignore {
+                return None;
+            }
+            self.find(method, path, true)
+        })
+    }
+
+    fn find<'r, 'p>(
+        &'r self,
+        method: &Method,
+        path: &'p str,
+        toggle_trailing_slash: bool,
+    ) -> Option<(&'r T, Params<'r, 'p>)> {
+        let found = self
+            .routes
+            .get(method)
+            .and_then(|tree| find_in_tree(tree, path, toggle_trailing_slash));
+        found
+            .or_else(|| {
+                if method != Method::HEAD {
+                    return None;
+                }
+                // HEAD can execute GET
+                self.routes
+                    .get(&Method::GET)
+                    .and_then(|tree| find_in_tree(tree, path, toggle_trailing_slash))
+            })
+            .or_else(|| find_in_tree(&self.any, path, toggle_trailing_slash))
+    }
+
+    pub fn try_resolve<'r, 'p>(
+        &'r self,
+        method: &Method,
+        path: &'p str,
+    ) -> Result<(&'r T, Params<'r, 'p>), ResolveError> {
+        if let Some(found) = self.resolve(method, path) {
+            return Ok(found);
+        }
+
+        if self.trailing_slash == TrailingSlash::Redirect && self.find(method, path, true).is_some()
+        {
+            let location = match path.strip_suffix('/') {
+                Some(path) => path.to_string(),
+                None => format!("{}/", path),
+            };
+            return Err(ResolveError::Redirect { location });
+        }
+
+        let mut allowed = self.allowed_methods(path);
+        allowed.retain(|m| m != method);
+        if allowed.is_empty() {
+            return Err(ResolveError::NotFound);
+        }
+        Err(ResolveError::MethodNotAllowed { allowed })
+    }
 
-    pub fn resolve(&self, method: &Method, path: &str) -> Option<(&T, Params)> {
-        // let path = path.to_lowercase();
-        self.routes.get(method).and_then(|tree| tree.find(path))
+    /// Returns the methods that have a route matching `path` (sorted by name), including `HEAD`
+    /// if there is a `GET` route. If a route registered with `any` matches, all standard methods
+    /// are returned (along with the extension methods that have a matching route).
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let toggle_trailing_slash = self.trailing_slash != TrailingSlash::Strict;
+        let matches = |tree: &Tree<T>| {
+            tree.find(path).is_some()
+                || (toggle_trailing_slash && tree.find_toggled_trailing_slash(path).is_some())
+        };
+        let mut allowed: Vec<Method> = self
+            .routes
+            .iter()
+            .filter(|&(_, tree)| matches(tree))
+            .map(|(m, _)| m.clone())
+            .collect();
+        if matches(&self.any) {
+            let standard = [
+                Method::OPTIONS,
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::HEAD,
+                Method::TRACE,
+                Method::CONNECT,
+                Method::PATCH,
+            ];
+            for method in &standard {
+                if !allowed.contains(method) {
+                    allowed.push(method.clone());
+                }
+            }
+        }
+        if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+            allowed.push(Method::HEAD);
+        }
+        allowed.sort_by(|lhs, rhs| lhs.as_str().cmp(rhs.as_str()));
+        allowed
     }
 }
 
-impl<'a, T> Default for Router<'a, T> {
+impl<T> Default for Router<T> {
     fn default() -> Self {
         let mut routes = HashMap::with_capacity(2);
         routes.insert(Method::GET, Tree::new());
         routes.insert(Method::POST, Tree::new());
-        Router { routes }
+        Router {
+            routes,
+            any: Tree::new(),
+            names: HashMap::new(),
+            trailing_slash: TrailingSlash::default(),
+            case_insensitive: false,
+        }
+    }
+}
+
+fn find_in_tree<'t, 'p, T>(
+    tree: &'t Tree<T>,
+    path: &'p str,
+    toggle_trailing_slash: bool,
+) -> Option<(&'t T, Params<'t, 'p>)> {
+    if toggle_trailing_slash {
+        tree.find_toggled_trailing_slash(path)
+    } else {
+        tree.find(path)
+    }
+}
+
+/// Prepends `prefix` (without a trailing slash) to `pattern`, a root `pattern` becomes the bare
+/// `prefix`.
+fn prefixed(prefix: &str, pattern: &str) -> String {
+    if pattern == "/" && !prefix.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{}{}", prefix, pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use std::sync::Arc;
+    use std::thread;
+    use {ResolveError, RouteError, Router, TrailingSlash, UrlError};
+
+    #[test]
+    fn method_not_allowed() {
+        let mut router = Router::default();
+        router.post("/foo", 1);
+        router.get("/foo", 2);
+        router.delete("/foo/:id", 3);
+
+        assert_eq!(
+            router.try_resolve(&Method::PUT, "/foo"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::GET, Method::HEAD, Method::POST],
+            })
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/foo/42"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::DELETE],
+            })
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/bar"),
+            Err(ResolveError::NotFound)
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/foo").map(|(v, _)| v),
+            Ok(&2)
+        );
+    }
+
+    #[test]
+    fn head_falls_back_to_get() {
+        let mut router = Router::default();
+        router.get("/foo", 1);
+        router.get("/bar", 2);
+        router.head("/bar", 3);
+
+        assert_eq!(
+            router.resolve(&Method::HEAD, "/foo").map(|(v, _)| v),
+            Some(&1)
+        );
+        assert_eq!(
+            router.resolve(&Method::HEAD, "/bar").map(|(v, _)| v),
+            Some(&3)
+        );
+        assert_eq!(router.resolve(&Method::HEAD, "/baz").map(|(v, _)| v), None);
+        assert_eq!(router.resolve(&Method::POST, "/foo").map(|(v, _)| v), None);
+        assert_eq!(
+            router.try_resolve(&Method::POST, "/foo"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::GET, Method::HEAD],
+            })
+        );
+    }
+
+    #[test]
+    fn trailing_slash_strict() {
+        let mut router = Router::default();
+        router.get("/foo", 1);
+        router.get("/bar/", 2);
+
+        assert_eq!(
+            router.resolve(&Method::GET, "/foo").map(|(v, _)| v),
+            Some(&1)
+        );
+        assert_eq!(router.resolve(&Method::GET, "/foo/").map(|(v, _)| v), None);
+        assert_eq!(router.resolve(&Method::GET, "/bar").map(|(v, _)| v), None);
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/foo/"),
+            Err(ResolveError::NotFound)
+        );
+    }
+
+    #[test]
+    fn trailing_slash_ignore() {
+        let mut router = Router::default();
+        router.set_trailing_slash(TrailingSlash::Ignore);
+        router.get("/foo", 1);
+        router.get("/bar/", 2);
+        router.post("/baz/", 3);
+
+        assert_eq!(
+            router.resolve(&Method::GET, "/foo/").map(|(v, _)| v),
+            Some(&1)
+        );
+        assert_eq!(
+            router.resolve(&Method::GET, "/bar").map(|(v, _)| v),
+            Some(&2)
+        );
+        assert_eq!(
+            router.resolve(&Method::GET, "/bar/").map(|(v, _)| v),
+            Some(&2)
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/baz"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::POST],
+            })
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut router = Router::default();
+        router.route_named("user", Method::GET, "/users/:id", 1);
+        router.get("/users/new", 2);
+        router.delete("/users/:id", 3);
+
+        assert_eq!(router.remove(&Method::GET, "/users/:name"), None);
+        assert_eq!(router.remove(&Method::GET, "/users/:id"), Some(1));
+        assert_eq!(router.resolve(&Method::GET, "/users/42"), None);
+        assert_eq!(
+            router.resolve(&Method::GET, "/users/new").map(|v| *v.0),
+            Some(2)
+        );
+        // the name is kept as long as the pattern is registered for any method
+        assert_eq!(
+            router.url_for("user", &[("id", "42")]),
+            Ok("/users/42".to_string())
+        );
+
+        assert_eq!(router.remove(&Method::DELETE, "/users/:id"), Some(3));
+        assert_eq!(
+            router.url_for("user", &[("id", "42")]),
+            Err(UrlError::UnknownRoute {
+                name: "user".to_string()
+            })
+        );
+        assert_eq!(router.remove(&Method::PUT, "/users/:id"), None);
+    }
+
+    #[test]
+    fn replace() {
+        let mut router = Router::default();
+        router.get("/posts(/:page)?", 1);
+        assert_eq!(router.replace(Method::GET, "/posts(/:page)?", 2), Some(1));
+        assert_eq!(
+            router.resolve(&Method::GET, "/posts").map(|v| *v.0),
+            Some(2)
+        );
+        assert_eq!(
+            router.resolve(&Method::GET, "/posts/2").map(|v| *v.0),
+            Some(2)
+        );
+
+        assert_eq!(router.replace(Method::GET, "/posts/:page/edit", 3), None);
+        assert_eq!(
+            router.resolve(&Method::GET, "/posts/1/edit").map(|v| *v.0),
+            Some(3)
+        );
+        assert_eq!(
+            router.try_replace(Method::GET, "/posts/:slug/comments", 4),
+            Err(RouteError::ConflictingParamNames {
+                pattern: "/posts/:slug/comments".to_string(),
+                existing: "page".to_string(),
+                name: "slug".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn routes() {
+        let mut router = Router::default();
+        router.post("/users", 1);
+        router.get("/users/:id<int>", 2);
+        router.get("/users", 3);
+        router.delete("/users/:id<int>", 4);
+        router.get("/posts(/:page)?", 5);
+
+        let mut admin = Router::default();
+        admin.get("/stats", 6);
+        router.mount("/admin", admin);
+
+        assert_eq!(
+            router
+                .routes()
+                .map(|(method, pattern, handler)| (method.as_str(), pattern, *handler))
+                .collect::<Vec<_>>(),
+            vec![
+                ("GET", "/admin/stats", 6),
+                ("GET", "/posts(/:page)?", 5),
+                ("GET", "/users", 3),
+                ("POST", "/users", 1),
+                ("DELETE", "/users/:id<int>", 4),
+                ("GET", "/users/:id<int>", 2),
+            ]
+        );
+
+        router.remove(&Method::GET, "/users");
+        assert_eq!(
+            router
+                .routes()
+                .map(|(method, pattern, _)| (method.as_str(), pattern))
+                .collect::<Vec<_>>(),
+            vec![
+                ("GET", "/admin/stats"),
+                ("GET", "/posts(/:page)?"),
+                ("POST", "/users"),
+                ("DELETE", "/users/:id<int>"),
+                ("GET", "/users/:id<int>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn freeze() {
+        let mut router = Router::default();
+        router.get("/users/:id<int>", 1);
+        router.get("/users/:name/", 2);
+        router.delete("/users/:id<int>", 3);
+        router.set_trailing_slash(TrailingSlash::Redirect);
+        router.freeze();
+
+        let (value, params) = router.resolve(&Method::HEAD, "/users/42").unwrap();
+        assert_eq!((*value, params.get("id")), (1, Some("42")));
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/users/rkusa"),
+            Err(ResolveError::Redirect {
+                location: "/users/rkusa/".to_string()
+            })
+        );
+        assert_eq!(
+            router.try_resolve(&Method::POST, "/users/42"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::DELETE, Method::GET, Method::HEAD],
+            })
+        );
+
+        router.get("/users/new", 4);
+        assert_eq!(
+            router.resolve(&Method::GET, "/users/new").map(|v| *v.0),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn any() {
+        let mut router = Router::default();
+        router.any("/proxy/*path", 1);
+        router.any("/users/new", 2);
+        router.get("/users/:id", 3);
+        router.get("/proxy/health", 4);
+
+        let resolve = |method, path| router.resolve(&method, path).map(|v| *v.0);
+        assert_eq!(resolve(Method::POST, "/proxy/api/users"), Some(1));
+        assert_eq!(resolve(Method::GET, "/proxy/api/users"), Some(1));
+        assert_eq!(resolve(Method::GET, "/proxy/health"), Some(4));
+        assert_eq!(resolve(Method::POST, "/proxy/health"), Some(1));
+        // routes for the request's method take precedence, even if less specific
+        assert_eq!(resolve(Method::GET, "/users/new"), Some(3));
+        assert_eq!(resolve(Method::HEAD, "/users/new"), Some(3));
+        assert_eq!(resolve(Method::DELETE, "/users/new"), Some(2));
+        assert_eq!(
+            router.try_resolve(&Method::DELETE, "/users/42"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::GET, Method::HEAD],
+            })
+        );
+
+        assert_eq!(
+            router.try_any("/users/:name/edit", 5),
+            Ok(()),
+            "any routes are independent of the routes of other methods"
+        );
+        assert_eq!(
+            router.any_routes().collect::<Vec<_>>(),
+            vec![
+                ("/proxy/*path", &1),
+                ("/users/:name/edit", &5),
+                ("/users/new", &2),
+            ]
+        );
+
+        let mut api = Router::default();
+        api.any("/*path", 6);
+        router.mount("/api", api);
+        assert_eq!(router.remove_any("/users/new"), Some(2));
+        router.freeze();
+        let resolve = |method, path| router.resolve(&method, path).map(|v| *v.0);
+        assert_eq!(resolve(Method::PUT, "/api/users"), Some(6));
+        assert_eq!(resolve(Method::DELETE, "/users/new"), None);
+    }
+
+    #[test]
+    fn extension_methods() {
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+        let mkcol = Method::from_bytes(b"MKCOL").unwrap();
+
+        let mut router = Router::default();
+        router.route(propfind.clone(), "/dav/*path", 1);
+        router.route(mkcol.clone(), "/dav/*path", 2);
+        router.get("/dav/*path", 3);
+        router.trace("/trace", 4);
+
+        assert_eq!(
+            router.resolve(&propfind, "/dav/docs/").map(|v| *v.0),
+            Some(1)
+        );
+        assert_eq!(
+            router
+                .resolve(&Method::from_bytes(b"MKCOL").unwrap(), "/dav/docs")
+                .map(|v| *v.0),
+            Some(2)
+        );
+        assert_eq!(
+            router.resolve(&Method::TRACE, "/trace").map(|v| *v.0),
+            Some(4)
+        );
+        assert_eq!(
+            router.try_resolve(&Method::from_bytes(b"LOCK").unwrap(), "/dav/docs"),
+            Err(ResolveError::MethodNotAllowed {
+                allowed: vec![Method::GET, Method::HEAD, mkcol.clone(), propfind.clone()],
+            })
+        );
+        assert_eq!(
+            router
+                .routes()
+                .map(|(method, pattern, _)| (method.as_str(), pattern))
+                .collect::<Vec<_>>(),
+            vec![
+                ("GET", "/dav/*path"),
+                ("MKCOL", "/dav/*path"),
+                ("PROPFIND", "/dav/*path"),
+                ("TRACE", "/trace"),
+            ]
+        );
+    }
+
+    #[test]
+    fn allowed_methods() {
+        let mut router = Router::default();
+        router.post("/foo", 1);
+        router.get("/foo", 2);
+        router.delete("/foo/:id", 3);
+        router.any("/bar/:id", 4);
+        router.route(Method::from_bytes(b"PROPFIND").unwrap(), "/bar/:id", 5);
+
+        assert_eq!(
+            router.allowed_methods("/foo"),
+            vec![Method::GET, Method::HEAD, Method::POST]
+        );
+        assert_eq!(router.allowed_methods("/foo/1"), vec![Method::DELETE]);
+        let allowed = router
+            .allowed_methods("/bar/1")
+            .into_iter()
+            .map(|method| method.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            allowed,
+            vec![
+                "CONNECT", "DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PROPFIND", "PUT",
+                "TRACE",
+            ]
+        );
+        assert!(router.allowed_methods("/foo/").is_empty());
+        router.set_trailing_slash(TrailingSlash::Ignore);
+        assert_eq!(
+            router.allowed_methods("/foo/"),
+            vec![Method::GET, Method::HEAD, Method::POST]
+        );
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let mut router = Router::default();
+        router.get("/Users/:name", 1);
+        assert_eq!(router.resolve(&Method::GET, "/users/John"), None);
+
+        router.set_case_insensitive(true);
+        router.put("/users/:name/avatar", 2);
+        let (value, params) = router.resolve(&Method::GET, "/users/John").unwrap();
+        assert_eq!(*value, 1);
+        assert_eq!(params.get("name"), Some("John"));
+        assert_eq!(
+            router
+                .resolve(&Method::PUT, "/USERS/John/Avatar")
+                .map(|v| *v.0),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn trailing_slash_redirect() {
+        let mut router = Router::default();
+        router.set_trailing_slash(TrailingSlash::Redirect);
+        router.get("/foo", 1);
+        router.get("/bar/", 2);
+        router.get("/", 3);
+
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/foo").map(|(v, _)| v),
+            Ok(&1)
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/foo/"),
+            Err(ResolveError::Redirect {
+                location: "/foo".to_string(),
+            })
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/bar"),
+            Err(ResolveError::Redirect {
+                location: "/bar/".to_string(),
+            })
+        );
+        assert_eq!(router.resolve(&Method::GET, "/bar").map(|(v, _)| v), None);
+        assert_eq!(
+            router.try_resolve(&Method::GET, "//"),
+            Err(ResolveError::Redirect {
+                location: "/".to_string(),
+            })
+        );
+        assert_eq!(
+            router.try_resolve(&Method::GET, "/baz/"),
+            Err(ResolveError::NotFound)
+        );
+    }
+
+    #[test]
+    fn owned_patterns() {
+        let mut router = Router::default();
+        for (i, resource) in ["users", "posts"].iter().enumerate() {
+            router.get(&format!("/{}/:id", resource), i);
+        }
+
+        let router = Arc::new(router);
+        let handle = {
+            let router = router.clone();
+            thread::spawn(move || {
+                router
+                    .resolve(&Method::GET, "/posts/42")
+                    .map(|(v, params)| (*v, params.get("id").map(String::from)))
+            })
+        };
+        assert_eq!(handle.join().unwrap(), Some((1, Some("42".to_string()))));
+    }
+
+    #[test]
+    fn resolve_uri_path() {
+        let mut router = Router::default();
+        router.get("/café", 1);
+        router.get("/städte/:stadt", 2);
+
+        let uri = "http://localhost/caf%C3%A9".parse::<http::Uri>().unwrap();
+        assert_eq!(
+            router.resolve(&Method::GET, uri.path()).map(|v| *v.0),
+            Some(1)
+        );
+
+        let uri = "http://localhost/st%C3%A4dte/K%C3%B6ln"
+            .parse::<http::Uri>()
+            .unwrap();
+        let (handler, params) = router.resolve(&Method::GET, uri.path()).unwrap();
+        assert_eq!(*handler, 2);
+        assert_eq!(params.get("stadt"), Some("Köln"));
+    }
+
+    #[test]
+    fn url_for() {
+        let mut router = Router::default();
+        router.route_named(
+            "comment",
+            Method::GET,
+            "/articles/:article/comments/:comment",
+            1,
+        );
+        assert_eq!(
+            router.url_for("comment", &[("article", "42"), ("comment", "13")]),
+            Ok("/articles/42/comments/13".to_string())
+        );
+        assert_eq!(
+            router.url_for("article", &[]),
+            Err(UrlError::UnknownRoute {
+                name: "article".to_string()
+            })
+        );
+        assert_eq!(
+            router.try_route_named("comment", Method::POST, "/comments", 2),
+            Err(RouteError::DuplicateName {
+                name: "comment".to_string()
+            })
+        );
+        assert_eq!(
+            router.resolve(&Method::POST, "/comments").map(|v| v.0),
+            None
+        );
+    }
+
+    #[test]
+    fn url_for_optional_root_segment() {
+        let mut router = Router::default();
+        router.route_named("home", Method::GET, "/:lang?", 1);
+        assert_eq!(router.resolve(&Method::GET, "/").map(|v| *v.0), Some(1));
+        assert_eq!(router.resolve(&Method::GET, "/en").map(|v| *v.0), Some(1));
+        assert_eq!(router.url_for("home", &[]), Ok("/".to_string()));
+        assert_eq!(
+            router.url_for("home", &[("lang", "en")]),
+            Ok("/en".to_string())
+        );
+    }
+
+    #[test]
+    fn mount() {
+        let mut posts = Router::default();
+        posts.get("/posts", 1);
+        posts.route_named("post", Method::GET, "/posts/:post", 2);
+
+        let mut router = Router::default();
+        router.get("/users/:user", 3);
+        router.mount("/users/:user/", posts);
+
+        assert_eq!(
+            router.resolve(&Method::GET, "/users/1/posts").map(|v| v.0),
+            Some(&1)
+        );
+        let (handler, params) = router.resolve(&Method::GET, "/users/1/posts/2").unwrap();
+        assert_eq!(*handler, 2);
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            vec![("user", "1"), ("post", "2")]
+        );
+        assert_eq!(
+            router.resolve(&Method::GET, "/users/1").map(|v| v.0),
+            Some(&3)
+        );
+        assert_eq!(
+            router.url_for("post", &[("user", "1"), ("post", "2")]),
+            Ok("/users/1/posts/2".to_string())
+        );
+    }
+
+    #[test]
+    fn mount_root_route() {
+        let mut other = Router::default();
+        other.route_named("index", Method::GET, "/", 1);
+        other.get("/users", 2);
+
+        let mut router = Router::default();
+        router.mount("/api/:version", other);
+        let (handler, params) = router.resolve(&Method::GET, "/api/v1").unwrap();
+        assert_eq!(*handler, 1);
+        assert_eq!(params.get("version"), Some("v1"));
+        assert_eq!(router.resolve(&Method::GET, "/api/v1/").map(|v| v.0), None);
+        assert_eq!(
+            router.resolve(&Method::GET, "/api/v1/users").map(|v| *v.0),
+            Some(2)
+        );
+        assert_eq!(
+            router.url_for("index", &[("version", "v1")]),
+            Ok("/api/v1".to_string())
+        );
+
+        let mut other = Router::default();
+        other.get("/", 1);
+        let mut router = Router::default();
+        router.mount("/", other);
+        assert_eq!(router.resolve(&Method::GET, "/").map(|v| *v.0), Some(1));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn mount_keeps_precedence() {
+        let mut other = Router::default();
+        other.get("/x/:id<re:[a-f0-9]+>", 1);
+        other.get("/x/:id<alpha>", 2);
+        assert_eq!(other.resolve(&Method::GET, "/x/abc").map(|v| *v.0), Some(1));
+
+        let mut router = Router::default();
+        router.mount("/api", other);
+        assert_eq!(
+            router.resolve(&Method::GET, "/api/x/abc").map(|v| *v.0),
+            Some(1)
+        );
+        assert_eq!(
+            router.resolve(&Method::GET, "/api/x/xyz").map(|v| *v.0),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn failed_mount_leaves_router_unchanged() {
+        let mut router = Router::default();
+        router.get("/api/foo", 1);
+        router.post("/api/foo", 2);
+        router.freeze();
+
+        let mut other = Router::default();
+        other.get("/a", 3);
+        other.post("/x", 4);
+        other.put("/y", 5);
+        other.delete("/z", 6);
+        other.any("/any", 7);
+        other.route_named("foo", Method::GET, "/foo", 8);
+        assert!(router.try_mount("/api", other).is_err());
+
+        let routes = router
+            .routes()
+            .map(|(method, pattern, handler)| (method.as_str(), pattern, *handler))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            routes,
+            vec![("GET", "/api/foo", 1), ("POST", "/api/foo", 2)]
+        );
+        assert_eq!(router.any_routes().count(), 0);
+        assert!(router.url_for("foo", &[]).is_err());
+        assert!(router.routes.values().all(|tree| tree.is_frozen()));
+        assert_eq!(router.allowed_methods("/api/x"), Vec::<Method>::new());
+    }
+
+    #[test]
+    fn failed_route_keeps_router_frozen() {
+        let mut router = Router::default();
+        router.get("/users/:id", 1);
+        router.post("/users", 1);
+        router.any("/health", 2);
+        router.freeze();
+
+        assert!(router.try_route(Method::GET, "/users/:id", 3).is_err());
+        assert!(router
+            .try_route(Method::GET, "/users/:name/posts", 3)
+            .is_err());
+        assert!(router.try_any("/health", 3).is_err());
+        assert!(router.routes.values().all(|tree| tree.is_frozen()));
+        assert!(router.any.is_frozen());
+
+        let mut other = Router::default();
+        other.get("/a", 4);
+        other.post("/b", 5);
+        other.any("/c", 6);
+        other.get("/users/:name/posts", 7);
+        assert!(router.try_mount("", other).is_err());
+        assert!(router.routes.values().all(|tree| tree.is_frozen()));
+        assert!(router.any.is_frozen());
+        assert_eq!(router.routes.len(), 2);
+        assert_eq!(router.resolve(&Method::GET, "/a").map(|v| v.0), None);
+    }
+
+    #[test]
+    fn mount_conflicts() {
+        let mut router = Router::default();
+        router.route_named("foo", Method::GET, "/api/foo", 1);
+
+        let mut other = Router::default();
+        other.get("/foo", 2);
+        assert_eq!(
+            router.try_mount("/api", other),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/api/foo".to_string()
+            })
+        );
+
+        let mut other = Router::default();
+        other.route_named("foo", Method::GET, "/bar", 2);
+        assert_eq!(
+            router.try_mount("/api", other),
+            Err(RouteError::DuplicateName {
+                name: "foo".to_string()
+            })
+        );
+
+        let mut other = Router::default();
+        other.get("/:id/:name", 2);
+        router.get("/users/:user/:id", 3);
+        assert_eq!(
+            router.try_mount("/users/:id", other),
+            Err(RouteError::InvalidPattern {
+                pattern: "/users/:id/:id/:name".to_string(),
+                reason: "duplicate parameter name",
+            })
+        );
     }
 }
@@ -0,0 +1,450 @@
+use http::Method;
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::BuildHasher;
+use std::path::Path;
+use toml::Spanned;
+
+use {LoadError, Router};
+
+/// A route as declared in a route file, e.g. in TOML:
+///
+/// ```toml
+/// [[routes]]
+/// method = "GET"
+/// pattern = "/api/*path"
+/// handler = "proxy"
+/// name = "api"
+/// metadata = { upstream = "http://localhost:8080" }
+/// ```
+///
+/// or in JSON: `{ "routes": [{ "method": "GET", "pattern": "/api/*path", ... }] }`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteDef {
+    pub method: String,
+    pub pattern: String,
+    /// The key the route's handler is looked up with in the registry.
+    pub handler: String,
+    /// The name to build the route's path with `url_for`.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Arbitrary settings for the handler, e.g. the upstream of a proxy route.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Provides the handlers for the routes of a route file.
+pub trait Registry<T> {
+    /// Returns the handler for `route`, or `None` if its handler key is unknown.
+    fn handler(&mut self, route: &RouteDef) -> Option<T>;
+}
+
+impl<T: Clone, S: BuildHasher> Registry<T> for HashMap<String, T, S> {
+    fn handler(&mut self, route: &RouteDef) -> Option<T> {
+        self.get(&route.handler).cloned()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlFile {
+    #[serde(default)]
+    routes: Vec<Spanned<RouteDef>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonFile<'a> {
+    #[serde(borrow, default)]
+    routes: Vec<&'a RawValue>,
+}
+
+impl<T> Router<T> {
+    /// Adds the routes of a route file, whose format is determined by its extension (`.toml` or
+    /// `.json`), with their handlers taken from `registry`. If an error is returned, the routes
+    /// declared before the failing one have already been added; to apply a route file either
+    /// completely or not at all, load it into a new router.
+    pub fn load_routes<P, R>(&mut self, path: P, registry: &mut R) -> Result<(), LoadError>
+    where
+        P: AsRef<Path>,
+        R: Registry<T>,
+    {
+        let path = path.as_ref();
+        let file = path.display().to_string();
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => RouteFormat::Toml,
+            Some("json") => RouteFormat::Json,
+            _ => {
+                return Err(LoadError::Parse {
+                    file,
+                    line: None,
+                    message: "unknown route file format (expected `.toml` or `.json`)".to_string(),
+                })
+            }
+        };
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => return Err(LoadError::Io { file, error }),
+        };
+        self.load_routes_from_str(&file, &source, format, registry)
+    }
+
+    /// Like `load_routes`, but with the content of the route file given as `source`. `file` is
+    /// only used for error messages.
+    pub fn load_routes_from_str<R>(
+        &mut self,
+        file: &str,
+        source: &str,
+        format: RouteFormat,
+        registry: &mut R,
+    ) -> Result<(), LoadError>
+    where
+        R: Registry<T>,
+    {
+        let routes = match format {
+            RouteFormat::Toml => parse_toml(file, source)?,
+            RouteFormat::Json => parse_json(file, source)?,
+        };
+
+        for (line, route) in routes {
+            let method = Method::from_bytes(route.method.as_bytes()).map_err(|_| {
+                LoadError::InvalidMethod {
+                    file: file.to_string(),
+                    line,
+                    method: route.method.clone(),
+                }
+            })?;
+            let handler = registry
+                .handler(&route)
+                .ok_or_else(|| LoadError::UnknownHandler {
+                    file: file.to_string(),
+                    line,
+                    key: route.handler.clone(),
+                })?;
+            let result = match route.name {
+                Some(ref name) => self.try_route_named(name, method, &route.pattern, handler),
+                None => self.try_route(method, &route.pattern, handler),
+            };
+            result.map_err(|error| LoadError::Route {
+                file: file.to_string(),
+                line,
+                error,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the routes of a TOML route file, together with the line each of them starts at.
+fn parse_toml(file: &str, source: &str) -> Result<Vec<(usize, RouteDef)>, LoadError> {
+    let parsed: TomlFile = toml::from_str(source).map_err(|err| LoadError::Parse {
+        file: file.to_string(),
+        line: err.span().map(|span| line_at(source, span.start)),
+        message: err.message().to_string(),
+    })?;
+    Ok(parsed
+        .routes
+        .into_iter()
+        .map(|route| (line_at(source, route.span().start), route.into_inner()))
+        .collect())
+}
+
+/// Parses the routes of a JSON route file, together with the line each of them starts at.
+fn parse_json(file: &str, source: &str) -> Result<Vec<(usize, RouteDef)>, LoadError> {
+    let parse_error = |line: usize, err: serde_json::Error| {
+        // the message of a `serde_json::Error` ends with the line and column it occurred at
+        let message = err.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(i) => message[..i].to_string(),
+            None => message,
+        };
+        LoadError::Parse {
+            file: file.to_string(),
+            line: Some(line + err.line().max(1) - 1),
+            message,
+        }
+    };
+
+    // the routes are kept as raw JSON first, to find out where they start in the file
+    let parsed: JsonFile = serde_json::from_str(source).map_err(|err| parse_error(1, err))?;
+    parsed
+        .routes
+        .into_iter()
+        .map(|raw| {
+            let line = line_at(
+                source,
+                raw.get().as_ptr() as usize - source.as_ptr() as usize,
+            );
+            serde_json::from_str(raw.get())
+                .map(|route| (line, route))
+                .map_err(|err| parse_error(line, err))
+        })
+        .collect()
+}
+
+/// The (1-based) line of the byte at `offset`.
+fn line_at(source: &str, offset: usize) -> usize {
+    source.as_bytes()[..offset]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, RouteDef, RouteFormat};
+    use http::Method;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use {LoadError, RouteError, Router};
+
+    const TOML: &str = r#"
+[[routes]]
+method = "GET"
+pattern = "/users/:id<int>"
+handler = "users.show"
+name = "user"
+
+[[routes]]
+method = "GET"
+pattern = "/api/*path"
+handler = "proxy"
+metadata = { upstream = "http://localhost:8080" }
+"#;
+
+    const JSON: &str = r#"{
+  "routes": [
+    {
+      "method": "GET",
+      "pattern": "/users/:id<int>",
+      "handler": "users.show",
+      "name": "user"
+    },
+    {
+      "method": "GET",
+      "pattern": "/api/*path",
+      "handler": "proxy",
+      "metadata": { "upstream": "http://localhost:8080" }
+    }
+  ]
+}"#;
+
+    /// Creates a handler describing its route, proxies use their upstream.
+    struct Handlers;
+
+    impl Registry<String> for Handlers {
+        fn handler(&mut self, route: &RouteDef) -> Option<String> {
+            match route.handler.as_str() {
+                "users.show" => Some("users.show".to_string()),
+                "proxy" => route.metadata.get("upstream").cloned(),
+                _ => None,
+            }
+        }
+    }
+
+    fn assert_loaded(router: &Router<String>) {
+        let (handler, params) = router.resolve(&Method::GET, "/users/42").unwrap();
+        assert_eq!(
+            (handler.as_str(), params.get("id")),
+            ("users.show", Some("42"))
+        );
+        let (handler, params) = router.resolve(&Method::GET, "/api/v1/users").unwrap();
+        assert_eq!(
+            (handler.as_str(), params.get("path")),
+            ("http://localhost:8080", Some("v1/users"))
+        );
+        assert_eq!(
+            router.url_for("user", &[("id", "42")]),
+            Ok("/users/42".to_string())
+        );
+    }
+
+    #[test]
+    fn load_toml() {
+        let mut router = Router::default();
+        router
+            .load_routes_from_str("routes.toml", TOML, RouteFormat::Toml, &mut Handlers)
+            .unwrap();
+        assert_loaded(&router);
+    }
+
+    #[test]
+    fn load_json() {
+        let mut router = Router::default();
+        router
+            .load_routes_from_str("routes.json", JSON, RouteFormat::Json, &mut Handlers)
+            .unwrap();
+        assert_loaded(&router);
+    }
+
+    #[test]
+    fn load_file() {
+        let dir = env::temp_dir().join(format!("router-load-file-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("routes.toml"), TOML).unwrap();
+        fs::write(dir.join("routes.json"), JSON).unwrap();
+        fs::write(dir.join("routes.yaml"), "").unwrap();
+
+        for file in &["routes.toml", "routes.json"] {
+            let mut router = Router::default();
+            router.load_routes(dir.join(file), &mut Handlers).unwrap();
+            assert_loaded(&router);
+        }
+
+        let mut router = Router::default();
+        let err = router
+            .load_routes(dir.join("routes.yaml"), &mut Handlers)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: unknown route file format (expected `.toml` or `.json`)",
+                dir.join("routes.yaml").display()
+            )
+        );
+        match router.load_routes(dir.join("missing.toml"), &mut Handlers) {
+            Err(LoadError::Io { .. }) => {}
+            result => panic!("expected an IO error, got {:?}", result),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn registry_map() {
+        let mut registry = HashMap::new();
+        registry.insert("users.show".to_string(), 1);
+        registry.insert("proxy".to_string(), 2);
+
+        let mut router = Router::default();
+        router
+            .load_routes_from_str("routes.toml", TOML, RouteFormat::Toml, &mut registry)
+            .unwrap();
+        assert_eq!(
+            router.resolve(&Method::GET, "/api/foo").map(|v| *v.0),
+            Some(2)
+        );
+    }
+
+    fn load_error(source: &str, format: RouteFormat) -> String {
+        let file = match format {
+            RouteFormat::Toml => "routes.toml",
+            RouteFormat::Json => "routes.json",
+        };
+        let mut router = Router::default();
+        router.get("/users/:name", "users.show".to_string());
+        router
+            .load_routes_from_str(file, source, format, &mut Handlers)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn errors() {
+        let route = |method: &str, pattern: &str, handler: &str| {
+            format!(
+                "\n[[routes]]\nmethod = \"{}\"\npattern = \"{}\"\nhandler = \"{}\"\n",
+                method, pattern, handler
+            )
+        };
+        let valid = route("GET", "/", "users.show");
+
+        assert_eq!(
+            load_error(
+                &format!("{}{}", valid, route("GET", "/", "users.list")),
+                RouteFormat::Toml
+            ),
+            "routes.toml:7: unknown handler `users.list`"
+        );
+        assert_eq!(
+            load_error(
+                &format!("{}{}", valid, route("GET", "/users/:id", "users.show")),
+                RouteFormat::Toml
+            ),
+            "routes.toml:7: cannot have different parameter names at the same position \
+             (`id` conflicts with existing `name`) in `/users/:id`"
+        );
+        assert_eq!(
+            load_error(&route("GET", "/users/:", "users.show"), RouteFormat::Toml),
+            "routes.toml:2: invalid route pattern `/users/:`: parameter without a name"
+        );
+        assert_eq!(
+            load_error(&route("GE T", "/", "users.show"), RouteFormat::Toml),
+            "routes.toml:2: invalid method `GE T`"
+        );
+        assert_eq!(
+            load_error(&format!("{}methd = \"GET\"\n", valid), RouteFormat::Toml),
+            "routes.toml:6: unknown field `methd`, expected one of `method`, `pattern`, \
+             `handler`, `name`, `metadata`"
+        );
+        assert_eq!(
+            load_error("[[routes]\n", RouteFormat::Toml),
+            "routes.toml:1: invalid table header\nexpected `.`, `]]`"
+        );
+
+        let json = |routes: &str| format!("{{\n  \"routes\": [\n{}\n  ]\n}}", routes);
+        let route = |pattern: &str, handler: &str| {
+            format!(
+                "    {{\n      \"method\": \"GET\",\n      \"pattern\": \"{}\",\n      \
+                 \"handler\": \"{}\"\n    }}",
+                pattern, handler
+            )
+        };
+        assert_eq!(
+            load_error(
+                &json(&format!(
+                    "{},\n{}",
+                    route("/", "users.show"),
+                    route("/", "users.show")
+                )),
+                RouteFormat::Json
+            ),
+            "routes.json:8: route `/` is already registered"
+        );
+        assert_eq!(
+            load_error(&json(&route("/", "users.list")), RouteFormat::Json),
+            "routes.json:3: unknown handler `users.list`"
+        );
+        assert_eq!(
+            load_error(
+                &json("    {\n      \"method\": \"GET\",\n      \"pattern\": \"/\"\n    }"),
+                RouteFormat::Json
+            ),
+            "routes.json:6: missing field `handler`"
+        );
+        assert_eq!(
+            load_error("{\n  \"routes\": [\n    {]\n}", RouteFormat::Json),
+            "routes.json:3: key must be a string"
+        );
+    }
+
+    #[test]
+    fn errors_are_matchable() {
+        let mut router = Router::default();
+        router.get("/", "users.show".to_string());
+        match router.load_routes_from_str(
+            "routes.toml",
+            "[[routes]]\nmethod = \"GET\"\npattern = \"/\"\nhandler = \"users.show\"\n",
+            RouteFormat::Toml,
+            &mut Handlers,
+        ) {
+            Err(LoadError::Route {
+                ref file,
+                line: 1,
+                error: RouteError::DuplicateRoute { ref pattern },
+            }) if file == "routes.toml" && pattern == "/" => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
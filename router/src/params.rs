@@ -0,0 +1,382 @@
+use error::ParamError;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::{self, FromStr};
+
+const INLINE_CAPACITY: usize = 4;
+
+/// The parameters captured while resolving a path, in the order they appear in the path. Names
+/// are borrowed from the route (`'r`) and values from the resolved path (`'p`), so no allocations
+/// are necessary unless a route has more than four params or a value has to be percent-decoded.
+/// Values are percent-decoded, the raw values (as they appeared in the path) are available via
+/// `get_raw`.
+#[derive(Clone, Default)]
+pub struct Params<'r, 'p> {
+    inline: [Entry<'r, 'p>; INLINE_CAPACITY],
+    spilled: Vec<Entry<'r, 'p>>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Entry<'r, 'p> {
+    name: &'r str,
+    raw: &'p str,
+    decoded: Cow<'p, str>,
+}
+
+impl<'r, 'p> Params<'r, 'p> {
+    pub fn new() -> Self {
+        Params::default()
+    }
+
+    /// Returns the percent-decoded value of the param with the given `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entry(name).map(|e| e.decoded.as_ref())
+    }
+
+    /// Returns the value of the param with the given `name` as it appeared in the path.
+    pub fn get_raw(&self, name: &str) -> Option<&'p str> {
+        self.entry(name).map(|e| e.raw)
+    }
+
+    /// Parses the percent-decoded value of the param with the given `name` into `T`.
+    pub fn get_as<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        parse(name, self.get(name))
+    }
+
+    /// Deserializes all params into `T`, e.g. a struct with a field per param.
+    #[cfg(feature = "serde")]
+    pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T, ParamError> {
+        T::deserialize(::de::ParamsDeserializer::new(self.iter()))
+    }
+
+    /// Iterates over all params as `(name, decoded value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries().map(|e| (e.name, e.decoded.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn into_owned(self) -> OwnedParams {
+        OwnedParams {
+            params: self
+                .entries()
+                .map(|e| OwnedEntry {
+                    name: e.name.to_string(),
+                    raw: e.raw.to_string(),
+                    decoded: e.decoded.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds a param from its raw value. Returns `false` (and does not add the param) if the value
+    /// is not properly percent-encoded or does not decode to valid UTF-8.
+    pub(crate) fn push(&mut self, name: &'r str, raw: &'p str) -> bool {
+        let decoded = match percent_decode(raw) {
+            Some(decoded) => decoded,
+            None => return false,
+        };
+        let entry = Entry { name, raw, decoded };
+        if self.len < INLINE_CAPACITY {
+            self.inline[self.len] = entry;
+        } else {
+            self.spilled.push(entry);
+        }
+        self.len += 1;
+        true
+    }
+
+    /// Returns the percent-decoded value of the most recently added param.
+    pub(crate) fn last(&self) -> Option<&str> {
+        self.entries().last().map(|e| e.decoded.as_ref())
+    }
+
+    /// Removes all params added after the first `len` ones.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.spilled.truncate(len.saturating_sub(INLINE_CAPACITY));
+            self.len = len;
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry<'r, 'p>> {
+        let inline = self.len.min(INLINE_CAPACITY);
+        self.inline[..inline].iter().chain(self.spilled.iter())
+    }
+
+    fn entry(&self, name: &str) -> Option<&Entry<'r, 'p>> {
+        self.entries().find(|e| e.name == name)
+    }
+}
+
+impl<'r, 'p> PartialEq for Params<'r, 'p> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.entries().eq(other.entries())
+    }
+}
+
+impl<'r, 'p> fmt::Debug for Params<'r, 'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An owned copy of `Params`, e.g. to store them beyond the lifetime of the resolved path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OwnedParams {
+    params: Vec<OwnedEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OwnedEntry {
+    name: String,
+    raw: String,
+    decoded: String,
+}
+
+impl OwnedParams {
+    /// Returns the percent-decoded value of the param with the given `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entry(name).map(|e| e.decoded.as_str())
+    }
+
+    /// Returns the value of the param with the given `name` as it appeared in the path.
+    pub fn get_raw(&self, name: &str) -> Option<&str> {
+        self.entry(name).map(|e| e.raw.as_str())
+    }
+
+    /// Parses the percent-decoded value of the param with the given `name` into `T`.
+    pub fn get_as<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        parse(name, self.get(name))
+    }
+
+    /// Deserializes all params into `T`, e.g. a struct with a field per param.
+    #[cfg(feature = "serde")]
+    pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T, ParamError> {
+        T::deserialize(::de::ParamsDeserializer::new(self.iter()))
+    }
+
+    /// Iterates over all params as `(name, decoded value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|e| (e.name.as_str(), e.decoded.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    fn entry(&self, name: &str) -> Option<&OwnedEntry> {
+        self.params.iter().find(|e| e.name == name)
+    }
+}
+
+impl<'r, 'p> From<Params<'r, 'p>> for OwnedParams {
+    fn from(params: Params<'r, 'p>) -> Self {
+        params.into_owned()
+    }
+}
+
+fn parse<T>(name: &str, value: Option<&str>) -> Result<T, ParamError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = value.ok_or_else(|| ParamError::Missing {
+        name: name.to_string(),
+    })?;
+    value.parse().map_err(|err: T::Err| ParamError::Invalid {
+        name: name.to_string(),
+        value: value.to_string(),
+        message: err.to_string(),
+    })
+}
+
+fn percent_decode(raw: &str) -> Option<Cow<'_, str>> {
+    if !raw.contains('%') {
+        return Some(Cow::Borrowed(raw));
+    }
+
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut iter = raw.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hi = iter.next().and_then(hex_value)?;
+            let lo = iter.next().and_then(hex_value)?;
+            bytes.push(hi << 4 | lo);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok().map(Cow::Owned)
+}
+
+/// Appends the percent-encoded `value` to `out`. Only unreserved characters are kept as they
+/// are (except for `.` and the `separator` following the param, which would otherwise end it),
+/// slashes are kept for catch-alls.
+pub(crate) fn percent_encode_into(
+    out: &mut String,
+    value: &str,
+    is_catch_all: bool,
+    separator: Option<char>,
+) {
+    for b in value.bytes() {
+        match b {
+            _ if separator == Some(b as char) => out.push_str(&format!("%{:02X}", b)),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'~' => out.push(b as char),
+            b'.' | b'/' if is_catch_all => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+}
+
+pub(crate) fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, percent_encode_into};
+    use {ParamError, Params};
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("foo").unwrap(), "foo");
+        assert_eq!(percent_decode("John%20Doe").unwrap(), "John Doe");
+        assert_eq!(percent_decode("a%2Fb").unwrap(), "a/b");
+        assert_eq!(percent_decode("caf%C3%A9").unwrap(), "café");
+        assert_eq!(percent_decode("caf%c3%a9").unwrap(), "café");
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(percent_decode("%"), None);
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%C3%28"), None);
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn encode() {
+        let encode = |value, is_catch_all| {
+            let mut out = String::new();
+            percent_encode_into(&mut out, value, is_catch_all, None);
+            out
+        };
+        assert_eq!(encode("foo-bar_1~", false), "foo-bar_1~");
+        assert_eq!(encode("John Doe", false), "John%20Doe");
+        assert_eq!(encode("a/b.json", false), "a%2Fb%2Ejson");
+        assert_eq!(encode("a/b.json", true), "a/b.json");
+        assert_eq!(encode("café", false), "caf%C3%A9");
+
+        let mut out = String::new();
+        percent_encode_into(&mut out, "2018-10", false, Some('-'));
+        assert_eq!(out, "2018%2D10");
+    }
+
+    #[test]
+    fn raw_and_decoded() {
+        let mut params = Params::new();
+        assert!(params.push("name", "John%20Doe"));
+        assert!(!params.push("invalid", "%FF"));
+        assert_eq!(params.get("name"), Some("John Doe"));
+        assert_eq!(params.get_raw("name"), Some("John%20Doe"));
+        assert_eq!(params.get("invalid"), None);
+        assert_eq!(params.len(), 1);
+    }
+
+    #[test]
+    fn spill() {
+        let names = ["a", "b", "c", "d", "e", "f"];
+        let values = ["1", "2", "3", "4", "5", "6"];
+        let mut params = Params::new();
+        for (name, value) in names.iter().zip(values.iter()) {
+            params.push(name, value);
+        }
+        assert_eq!(params.len(), 6);
+        assert_eq!(params.get("f"), Some("6"));
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            names
+                .iter()
+                .cloned()
+                .zip(values.iter().cloned())
+                .collect::<Vec<_>>()
+        );
+
+        params.truncate(5);
+        assert_eq!(params.get("f"), None);
+        assert_eq!(params.get("e"), Some("5"));
+        params.truncate(2);
+        assert_eq!(params.len(), 2);
+        assert_eq!(params.get("c"), None);
+        params.push("g", "7");
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            vec![("a", "1"), ("b", "2"), ("g", "7")]
+        );
+    }
+
+    #[test]
+    fn into_owned() {
+        let mut params = Params::new();
+        params.push("id", "42");
+        params.push("name", "John%20Doe");
+        let owned = params.into_owned();
+        assert_eq!(owned.get("id"), Some("42"));
+        assert_eq!(owned.get("name"), Some("John Doe"));
+        assert_eq!(owned.get_raw("name"), Some("John%20Doe"));
+        assert_eq!(owned.len(), 2);
+    }
+
+    #[test]
+    fn get_as() {
+        let mut params = Params::new();
+        params.push("id", "42");
+        params.push("name", "John%20Doe");
+        assert_eq!(params.get_as::<u64>("id"), Ok(42));
+        assert_eq!(params.get_as::<String>("name"), Ok("John Doe".to_string()));
+        assert_eq!(
+            params.get_as::<u64>("page"),
+            Err(ParamError::Missing {
+                name: "page".to_string()
+            })
+        );
+        assert_eq!(
+            params.into_owned().get_as::<u64>("name"),
+            Err(ParamError::Invalid {
+                name: "name".to_string(),
+                value: "John Doe".to_string(),
+                message: "invalid digit found in string".to_string(),
+            })
+        );
+    }
+}
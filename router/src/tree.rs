@@ -1,40 +1,243 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::mem;
+use std::{iter, mem, str};
 
-pub type Params = HashMap<String, String>;
+use constraint::Constraint;
+use error::{RouteError, UrlError};
+use params::{hex_value, percent_encode_into, Params};
+
+mod frozen;
+
+use self::frozen::Matcher;
 
 #[derive(Debug)]
-pub struct Tree<'a, T>(Option<Node<'a, T>>);
+pub struct Tree<T> {
+    root: Option<Node<usize>>,
+    /// All routes as `(pattern, value)` pairs in the order they have been added. The nodes only
+    /// store indices into it, since a pattern with optional segments is added to multiple nodes.
+    routes: Vec<(String, T)>,
+    /// The expansions of all routes with their static parts lowercased (see `fold_case`), along
+    /// with how many routes they belong to. Used to detect routes that only differ in case.
+    folded: HashMap<String, usize>,
+    /// Whether static parts of the patterns are matched case-insensitively.
+    ignore_case: bool,
+    /// A flattened copy of `root` used to find routes, until routes are added or removed.
+    frozen: Option<Matcher>,
+}
 
 #[derive(Debug, PartialEq)]
-pub struct Node<'a, T> {
-    path: &'a str,
+pub struct Node<T> {
+    path: String,
     value: Option<T>,
-    childs: HashMap<char, Node<'a, T>>,
-    wildcard: Option<Param<'a, T>>,
+    childs: HashMap<char, Node<T>>,
+    /// Params at this position; constrained ones (in the order they have been added) are tried
+    /// before the unconstrained one, which is always last.
+    wildcards: Vec<Param<T>>,
+    catch_all: Option<CatchAll<T>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Param<T> {
+    name: String,
+    constraint: Option<Constraint>,
+    node: Box<Node<T>>,
+}
+
+/// Reasons why a path could not be added to an existing node.
+#[derive(Debug, PartialEq)]
+enum Conflict {
+    ParamName { existing: String, name: String },
+    Duplicate,
 }
 
+/// A trailing `*name` segment that captures the remainder of the path (including slashes).
+/// It has the lowest priority and is only used if neither a static child nor a param matches.
 #[derive(Debug, PartialEq)]
-pub struct Param<'a, T> {
-    name: &'a str,
-    node: Box<Node<'a, T>>,
+pub struct CatchAll<T> {
+    name: String,
+    value: Option<T>,
 }
 
-impl<'a, T> Tree<'a, T> {
+impl<T> Tree<T> {
     pub fn new() -> Self {
-        Tree(None)
+        Tree {
+            root: None,
+            routes: Vec::new(),
+            folded: HashMap::new(),
+            ignore_case: false,
+            frozen: None,
+        }
+    }
+
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    #[allow(dead_code)]
+    pub fn add_path(&mut self, path: &str, value: T) {
+        if let Err(err) = self.try_add_path(path, value) {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn try_add_path(&mut self, pattern: &str, value: T) -> Result<(), RouteError> {
+        let invalid = |reason| RouteError::InvalidPattern {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        let paths = expand_optional_segments(pattern).map_err(invalid)?;
+        for path in &paths {
+            validate_pattern(path).map_err(invalid)?;
+        }
+        // with case-insensitive matching, it would depend on the request's casing which of two
+        // routes that only differ in case matches
+        let folded = paths.iter().map(|path| fold_case(path)).collect::<Vec<_>>();
+        if self.ignore_case && folded.iter().any(|path| self.folded.contains_key(path)) {
+            return Err(RouteError::DuplicateRoute {
+                pattern: pattern.to_string(),
+            });
+        }
+
+        let index = self.routes.len();
+        for (i, path) in paths.iter().enumerate() {
+            let result = match self.root {
+                Some(ref mut node) => node.add_path(path, Some(index)),
+                None => {
+                    self.root = Some(Node::new(path, Some(index)));
+                    Ok(())
+                }
+            };
+
+            if let Err(conflict) = result {
+                // undo the expansions that have already been added
+                self.remove_paths(&paths[..i]);
+
+                return Err(match conflict {
+                    Conflict::ParamName { existing, name } => RouteError::ConflictingParamNames {
+                        pattern: pattern.to_string(),
+                        existing,
+                        name,
+                    },
+                    Conflict::Duplicate => RouteError::DuplicateRoute {
+                        pattern: pattern.to_string(),
+                    },
+                });
+            }
+        }
+
+        // a failed insert leaves the tree as it was, so the frozen copy is only discarded now
+        self.frozen = None;
+        for path in folded {
+            *self.folded.entry(path).or_insert(0) += 1;
+        }
+        self.routes.push((pattern.to_string(), value));
+        Ok(())
+    }
+
+    /// Removes the route with the given pattern (as it has been added) and returns its value.
+    pub fn remove(&mut self, pattern: &str) -> Option<T> {
+        let index = self.routes.iter().position(|route| route.0 == pattern)?;
+        let paths = expand_optional_segments(pattern).expect("pattern has been validated");
+        self.frozen = None;
+        self.remove_paths(&paths);
+        for path in &paths {
+            if let Entry::Occupied(mut entry) = self.folded.entry(fold_case(path)) {
+                *entry.get_mut() -= 1;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+        if let Some(ref mut node) = self.root {
+            node.reindex(index);
+        }
+        Some(self.routes.remove(index).1)
+    }
+
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.routes.iter().any(|route| route.0 == pattern)
+    }
+
+    /// Returns the value of the route with the given pattern (as it has been added).
+    pub fn get_mut(&mut self, pattern: &str) -> Option<&mut T> {
+        self.routes
+            .iter_mut()
+            .find(|route| route.0 == pattern)
+            .map(|route| &mut route.1)
+    }
+
+    fn remove_paths(&mut self, paths: &[String]) {
+        if let Some(ref mut node) = self.root {
+            for path in paths {
+                node.remove(path, None);
+            }
+        }
+        let is_empty = match self.root {
+            Some(ref node) => node.is_empty(),
+            None => false,
+        };
+        if is_empty {
+            self.root = None;
+        }
+    }
+
+    /// Flattens the tree into a structure that is faster to search. Routes added or removed
+    /// afterwards are found by searching the tree again, until it is frozen again.
+    pub fn freeze(&mut self) {
+        self.frozen = self.root.as_ref().map(Matcher::new);
     }
 
-    pub fn add_path(&mut self, path: &'a str, value: T) {
-        match self.0 {
-            Some(ref mut node) => node.add_path(path, Some(value)),
-            None => self.0 = Some(Node::new(path, Some(value))),
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
+    pub fn find<'t, 'p>(&'t self, path: &'p str) -> Option<(&'t T, Params<'t, 'p>)> {
+        self.find_with(path, false)
+    }
+
+    /// Finds the route for `path` with its trailing slash removed or, if it does not have one,
+    /// added.
+    pub fn find_toggled_trailing_slash<'t, 'p>(
+        &'t self,
+        path: &'p str,
+    ) -> Option<(&'t T, Params<'t, 'p>)> {
+        if path.is_empty() || path == "/" {
+            None
+        } else if let Some(path) = path.strip_suffix('/') {
+            self.find_with(path, false)
+        } else {
+            self.find_with(path, true)
         }
     }
 
-    pub fn find(&self, path: &str) -> Option<(&T, Params)> {
-        let params = HashMap::new();
-        self.0.as_ref().and_then(|node| node.find(path, params))
+    fn find_with<'t, 'p>(
+        &'t self,
+        path: &'p str,
+        add_slash: bool,
+    ) -> Option<(&'t T, Params<'t, 'p>)> {
+        let mut params = Params::new();
+        let index = match self.frozen {
+            Some(ref matcher) => matcher.find(path, &mut params, add_slash, self.ignore_case),
+            None => self
+                .root
+                .as_ref()
+                .and_then(|node| node.find(path, &mut params, add_slash, self.ignore_case))
+                .cloned(),
+        };
+        index.map(|index| (&self.routes[index].1, params))
+    }
+
+    /// Returns all routes as `(pattern, value)` pairs in the order they have been added.
+    pub fn routes(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.routes
+            .iter()
+            .map(|(pattern, value)| (pattern.as_str(), value))
+    }
+
+    /// Consumes the tree and returns all its routes as `(pattern, value)` pairs in the order they
+    /// have been added, so that re-adding them keeps the precedence of constrained params.
+    pub fn into_routes(self) -> Vec<(String, T)> {
+        self.routes
     }
 
     #[cfg(test)]
@@ -43,225 +246,862 @@ impl<'a, T> Tree<'a, T> {
     }
 }
 
-impl<'a, T> Node<'a, T> {
-    fn new(path: &'a str, value: Option<T>) -> Self {
+impl<T> Node<T> {
+    fn new(path: &str, value: Option<T>) -> Self {
         // extract params from path
         let mut value = value;
         let mut actual_path = None;
-        let mut wildcard = None;
-        let mut is_new_path_segment = false;
-        for (i, ch) in path.chars().enumerate() {
-            match ch {
-                '/' => is_new_path_segment = true,
-                ':' if is_new_path_segment => {
-                    let (left, right) = path.split_at(i);
-                    actual_path = Some(left);
-                    wildcard = Some(Param::new(&right, value.take()));
-                    break;
+        let mut wildcards = Vec::new();
+        let mut catch_all = None;
+        let mut prev = None;
+        for (i, ch) in path.char_indices() {
+            if is_param_start(prev, &path[i..]) {
+                let (left, right) = path.split_at(i);
+                actual_path = Some(left);
+                if ch == '*' {
+                    catch_all = Some(CatchAll::new(right, value.take()));
+                } else {
+                    wildcards.push(Param::new(right, value.take()));
                 }
-                _ if is_new_path_segment => is_new_path_segment = false,
-                _ => {}
+                break;
             }
+            prev = Some(ch);
         }
 
         Node {
-            path: actual_path.unwrap_or(path),
+            path: actual_path.unwrap_or(path).to_string(),
             value,
             childs: HashMap::new(),
-            wildcard,
+            wildcards,
+            catch_all,
         }
     }
 
-    fn add_path(&mut self, path: &'a str, value: Option<T>) {
-        let mut path = path;
-
-        // split self
-        let split_at = {
-            // iterate path and compare it agains current tree
+    fn add_path(&mut self, path: &str, value: Option<T>) -> Result<(), Conflict> {
+        // iterate path and compare it agains current tree, stop at the first param
+        let mut split_at = 0;
+        let mut is_param = false;
+        {
             let mut chars = self.path.chars();
-            let mut is_new_path_segment = false;
-            let mut i = 0;
-            for lhs in path.chars() {
-                match lhs {
-                    '/' => is_new_path_segment = true,
-                    ':' if is_new_path_segment => {
-                        // a param has been found -> continue in param node
-                        let (_, right) = path.split_at(i);
-                        if let Some(ref mut param) = self.wildcard {
-                            param.add_path(right, value);
-                        } else {
-                            self.wildcard = Some(Param::new(right, value));
-                        }
-                        return;
-                    }
-                    _ if is_new_path_segment => is_new_path_segment = false,
-                    _ => {}
+            let mut prev = None;
+            for (i, lhs) in path.char_indices() {
+                if is_param_start(prev, &path[i..]) {
+                    is_param = true;
+                    break;
                 }
 
-                // compare position with current node's path
-                if let Some(rhs) = chars.next() {
-                    if lhs != rhs {
-                        break;
-                    }
-                } else {
-                    break;
+                // compare position with current node's path, split_at is a byte offset
+                match chars.next() {
+                    Some(rhs) if lhs == rhs => split_at = i + lhs.len_utf8(),
+                    _ => break,
                 }
-                i += 1;
+                prev = Some(lhs);
             }
-            i
-        };
+        }
 
         if split_at < self.path.len() {
             // branch self
-            let (left, right) = self.path.split_at(split_at);
+            let right = self.path.split_off(split_at);
             let right_first_char = right.chars().next().unwrap();
             let node = Node {
                 path: right,
                 value: self.value.take(),
-                childs: mem::replace(&mut self.childs, HashMap::new()),
-                wildcard: self.wildcard.take(),
+                childs: mem::take(&mut self.childs),
+                wildcards: mem::take(&mut self.wildcards),
+                catch_all: self.catch_all.take(),
             };
 
-            self.path = left;
             self.childs.insert(right_first_char, node);
+        }
 
-            // add new node
-            if path != "" {
-                let (_, right) = path.split_at(split_at);
-                self.childs
-                    .insert(right.chars().next().unwrap(), Node::new(right, value));
-            } else {
+        let (_, path) = path.split_at(split_at);
+        let first_char = match path.chars().next() {
+            Some(ch) => ch,
+            None => {
+                if self.value.is_some() {
+                    return Err(Conflict::Duplicate);
+                }
                 self.value = value;
+                return Ok(());
+            }
+        };
+
+        if is_param && first_char == '*' {
+            // a catch-all has been found -> it always ends the path
+            match self.catch_all {
+                Some(ref mut catch_all) => catch_all.add_path(path, value),
+                None => {
+                    self.catch_all = Some(CatchAll::new(path, value));
+                    Ok(())
+                }
+            }
+        } else if is_param {
+            // a param has been found -> continue in the param node with the same constraint
+            let (_, constraint, _) = extract_param(path).expect("pattern has been validated");
+            let existing = self
+                .wildcards
+                .iter_mut()
+                .find(|param| param.constraint.as_ref().map(Constraint::as_str) == constraint);
+            match existing {
+                Some(param) => param.add_path(path, value),
+                None => {
+                    let param = Param::new(path, value);
+                    let unconstrained = self
+                        .wildcards
+                        .last()
+                        .is_some_and(|p| p.constraint.is_none());
+                    if param.constraint.is_some() && unconstrained {
+                        let i = self.wildcards.len() - 1;
+                        self.wildcards.insert(i, param);
+                    } else {
+                        self.wildcards.push(param);
+                    }
+                    Ok(())
+                }
             }
-            return;
         } else {
-            let (_, right) = path.split_at(split_at);
-            path = right;
+            match self.childs.entry(first_char) {
+                Entry::Occupied(mut entry) => entry.get_mut().add_path(path, value),
+                Entry::Vacant(entry) => {
+                    entry.insert(Node::new(path, value));
+                    Ok(())
+                }
+            }
         }
+    }
 
-        let first_char = match path.chars().next() {
-            Some(ch) => ch,
-            None => return,
+    /// If `add_slash` is set, the path is matched as if it had a trailing slash, which allows
+    /// to look up `/foo/` for `/foo` without allocating a new path. If `ignore_case` is set, the
+    /// static parts are compared case-insensitively (params keep the casing of the path).
+    /// Non-ASCII characters of the static parts also match their percent-encoded form.
+    fn find<'t, 'p>(
+        &'t self,
+        path: &'p str,
+        params: &mut Params<'t, 'p>,
+        add_slash: bool,
+        ignore_case: bool,
+    ) -> Option<&'t T> {
+        let path = match strip_prefix(path, &self.path, ignore_case) {
+            Some(path) => path,
+            None => {
+                if add_slash && strip_prefix(&self.path, path, ignore_case) == Some("/") {
+                    // the path fully matches with the added slash
+                    let rest = &path[path.len()..];
+                    return self.value.as_ref().or_else(|| {
+                        self.catch_all
+                            .as_ref()
+                            .and_then(|catch_all| catch_all.find(rest, params))
+                    });
+                }
+                return None;
+            }
+        };
+
+        // static childs are tried first, then the param and finally the catch-all
+        let found = match next_char(path).map(|(ch, _)| ch) {
+            None if add_slash => self
+                .childs
+                .get(&'/')
+                .and_then(|child| child.find(path, params, add_slash, ignore_case)),
+            None => self.value.as_ref(),
+            Some(first_char) => self
+                .childs_for(first_char, ignore_case)
+                .find_map(|child| child.find(path, params, add_slash, ignore_case))
+                .or_else(|| {
+                    self.wildcards
+                        .iter()
+                        .find_map(|param| param.find(path, params, add_slash, ignore_case))
+                }),
         };
 
-        if self.childs.contains_key(&first_char) {
-            let node = self.childs.get_mut(&first_char).unwrap();
-            node.add_path(path, value);
+        found.or_else(|| {
+            self.catch_all
+                .as_ref()
+                .and_then(|catch_all| catch_all.find(path, params))
+        })
+    }
+}
+
+impl<T> Node<T> {
+    /// The childs a path starting with `ch` might continue in, the exact match first.
+    fn childs_for(&self, ch: char, ignore_case: bool) -> impl Iterator<Item = &Node<T>> {
+        let others = if ignore_case {
+            Some(
+                self.childs
+                    .iter()
+                    .filter(move |&(&key, _)| key != ch && eq_ignore_case(key, ch))
+                    .map(|(_, child)| child),
+            )
         } else {
-            self.childs.insert(first_char, Node::new(path, value));
+            None
+        };
+        self.childs
+            .get(&ch)
+            .into_iter()
+            .chain(others.into_iter().flatten())
+    }
+
+    /// Whether a path continuing with `ch` could match this node (ignoring its params).
+    fn continues_with(&self, ch: char, ignore_case: bool) -> bool {
+        match self.path.chars().next() {
+            Some(first_char) if ignore_case => eq_ignore_case(first_char, ch),
+            Some(first_char) => first_char == ch,
+            None => self.childs_for(ch, ignore_case).next().is_some(),
         }
     }
 
-    fn find(&self, path: &str, params: Params) -> Option<(&T, Params)> {
-        if !path.starts_with(self.path) {
+    /// Removes the value of the given (already validated) pattern. Nodes that end up empty are
+    /// pruned and a node that only has a single child left is merged with it, so that the tree
+    /// looks as if the pattern has never been added. `prev` is the last character of the pattern
+    /// up to this node.
+    fn remove(&mut self, path: &str, prev: Option<char>) -> Option<T> {
+        if !path.starts_with(&self.path) {
             return None;
         }
 
         let (_, path) = path.split_at(self.path.len());
-
-        let first_char = match path.chars().next() {
-            Some(ch) => ch,
-            None => return self.value.as_ref().map(|v| (v, params)),
+        let prev = self.path.chars().next_back().or(prev);
+        let is_param_start = is_param_start(prev, path);
+        let removed = match path.chars().next() {
+            None => self.value.take(),
+            Some(':') if is_param_start => {
+                let (name, constraint, rest) =
+                    extract_param(path).expect("pattern has been validated");
+                let prev = path[..path.len() - rest.len()].chars().next_back();
+                let i = self.wildcards.iter().position(|param| {
+                    param.name == name
+                        && param.constraint.as_ref().map(Constraint::as_str) == constraint
+                })?;
+                let removed = self.wildcards[i].node.remove(rest, prev);
+                if self.wildcards[i].node.is_empty() {
+                    self.wildcards.remove(i);
+                }
+                removed
+            }
+            Some('*') if is_param_start => match self.catch_all {
+                Some(ref catch_all) if catch_all.name == path[1..] => {
+                    self.catch_all.take().and_then(|catch_all| catch_all.value)
+                }
+                _ => None,
+            },
+            Some(first_char) => {
+                let removed = self.childs.get_mut(&first_char)?.remove(path, prev);
+                if self.childs[&first_char].is_empty() {
+                    self.childs.remove(&first_char);
+                }
+                removed
+            }
         };
 
-        if let Some(child) = self.childs.get(&first_char) {
-            child.find(path, params)
-        } else if self.wildcard.is_some() {
-            self.wildcard.as_ref().unwrap().find(path, params)
-        } else {
-            None
+        if self.value.is_none()
+            && self.wildcards.is_empty()
+            && self.catch_all.is_none()
+            && self.childs.len() == 1
+        {
+            let first_char = *self.childs.keys().next().unwrap();
+            let child = self.childs.remove(&first_char).unwrap();
+            self.path.push_str(&child.path);
+            self.value = child.value;
+            self.childs = child.childs;
+            self.wildcards = child.wildcards;
+            self.catch_all = child.catch_all;
+        }
+
+        removed
+    }
+
+    fn is_empty(&self) -> bool {
+        self.value.is_none()
+            && self.childs.is_empty()
+            && self.wildcards.is_empty()
+            && self.catch_all.is_none()
+    }
+}
+
+impl Node<usize> {
+    /// Updates the route indices after the route at `removed` has been removed.
+    fn reindex(&mut self, removed: usize) {
+        let reindex = |index: &mut usize| {
+            if *index > removed {
+                *index -= 1;
+            }
+        };
+        if let Some(ref mut index) = self.value {
+            reindex(index);
+        }
+        for child in self.childs.values_mut() {
+            child.reindex(removed);
+        }
+        for param in &mut self.wildcards {
+            param.node.reindex(removed);
+        }
+        if let Some(CatchAll {
+            value: Some(ref mut index),
+            ..
+        }) = self.catch_all
+        {
+            reindex(index);
         }
     }
 }
 
-impl<'a, T> Param<'a, T> {
-    fn new(path: &'a str, value: Option<T>) -> Self {
-        let (name, path) = extract_param_name(path);
+impl<T> Param<T> {
+    fn new(path: &str, value: Option<T>) -> Self {
+        let (name, constraint, path) = extract_param(path).expect("pattern has been validated");
         Param {
-            name,
+            name: name.to_string(),
+            constraint: constraint.map(|constraint| {
+                Constraint::parse(constraint).expect("pattern has been validated")
+            }),
             node: Box::new(Node::new(path, value)),
         }
     }
 
-    fn add_path(&mut self, path: &'a str, value: Option<T>) {
-        let (name, path) = extract_param_name(path);
+    fn add_path(&mut self, path: &str, value: Option<T>) -> Result<(), Conflict> {
+        let (name, _, path) = extract_param(path).expect("pattern has been validated");
         if name != self.name {
-            // TODO: really panic?
-            panic!("cannot have different parameter names at the same position");
+            return Err(Conflict::ParamName {
+                existing: self.name.clone(),
+                name: name.to_string(),
+            });
         }
-        self.node.add_path(path, value);
+        self.node.add_path(path, value)
     }
 
-    fn find(&self, path: &str, mut params: Params) -> Option<(&T, Params)> {
-        let (value, path) = split_at_next_param_delimiter(path);
-        params.insert(self.name.to_string(), value.to_string());
-        self.node.find(path, params)
+    fn find<'t, 'p>(
+        &'t self,
+        path: &'p str,
+        params: &mut Params<'t, 'p>,
+        add_slash: bool,
+        ignore_case: bool,
+    ) -> Option<&'t T> {
+        // the value ends at the latest at the end of the segment, but might also end earlier at
+        // any literal the route continues with (e.g. the `@` in `/:name@:version`), shortest first
+        let end = path.find('/').unwrap_or(path.len());
+        let ends = char_indices(&path[..end])
+            .skip(1)
+            .filter(|&(_, ch)| self.node.continues_with(ch, ignore_case))
+            .map(|(i, _)| i)
+            .chain(Some(end));
+
+        let len = params.len();
+        for i in ends {
+            let (value, rest) = path.split_at(i);
+            if value.is_empty() || !params.push(&self.name, value) {
+                continue;
+            }
+            let is_allowed = match self.constraint {
+                Some(ref constraint) => {
+                    params.last().is_some_and(|value| constraint.matches(value))
+                }
+                None => true,
+            };
+            if is_allowed {
+                if let Some(found) = self.node.find(rest, params, add_slash, ignore_case) {
+                    return Some(found);
+                }
+            }
+            params.truncate(len);
+        }
+        None
     }
 
     #[cfg(test)]
     fn find_test(&self, path: &str) -> Option<&T> {
-        self.find(path, HashMap::new()).map(|v| v.0)
+        self.find(path, &mut Params::new(), false, false)
+    }
+}
+
+impl<T> CatchAll<T> {
+    fn new(path: &str, value: Option<T>) -> Self {
+        let (asterisk, name) = path.split_at(1);
+        assert_eq!(asterisk, "*");
+        CatchAll {
+            name: name.to_string(),
+            value,
+        }
+    }
+
+    fn add_path(&mut self, path: &str, value: Option<T>) -> Result<(), Conflict> {
+        let (_, name) = path.split_at(1);
+        if name != self.name {
+            return Err(Conflict::ParamName {
+                existing: self.name.clone(),
+                name: name.to_string(),
+            });
+        }
+        if self.value.is_some() {
+            return Err(Conflict::Duplicate);
+        }
+        self.value = value;
+        Ok(())
+    }
+
+    fn find<'t, 'p>(&'t self, path: &'p str, params: &mut Params<'t, 'p>) -> Option<&'t T> {
+        let value = self.value.as_ref()?;
+        if !params.push(&self.name, path) {
+            return None;
+        }
+        Some(value)
+    }
+}
+
+fn validate_pattern(path: &str) -> Result<(), &'static str> {
+    let mut names = Vec::new();
+    let mut rest = path;
+    let mut prev = None;
+    while let Some(ch) = rest.chars().next() {
+        if !is_param_start(prev, rest) {
+            prev = Some(ch);
+            rest = &rest[ch.len_utf8()..];
+            continue;
+        }
+
+        match ch {
+            ':' => {
+                let (name, constraint, remaining) = extract_param(rest)?;
+                if name.is_empty() {
+                    return Err("parameter without a name");
+                }
+                if let Some(constraint) = constraint {
+                    if constraint.is_empty() {
+                        return Err("empty parameter constraint");
+                    }
+                    if remaining.starts_with(is_name_char) {
+                        return Err("parameter constraint must end the parameter");
+                    }
+                    Constraint::parse(constraint)?;
+                }
+                if is_param_start(Some('>'), remaining) {
+                    return Err("parameters must be separated by a literal");
+                }
+                if names.contains(&name) {
+                    return Err("duplicate parameter name");
+                }
+                names.push(name);
+                prev = rest[..rest.len() - remaining.len()].chars().next_back();
+                rest = remaining;
+            }
+            _ => {
+                let name = &rest[1..];
+                if name.is_empty() {
+                    return Err("catch-all without a name");
+                }
+                if name.contains('/') {
+                    return Err("catch-all must be the last segment");
+                }
+                if !name.chars().all(is_name_char) {
+                    return Err("invalid catch-all name");
+                }
+                if names.contains(&name) {
+                    return Err("duplicate parameter name");
+                }
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Expands the optional parts of a pattern into all the concrete patterns it matches, e.g.
+/// `/posts(/:page)?` into `/posts/:page` and `/posts`. A param followed by a `?` (like
+/// `/docs/:lang?`) is short for an optional group of the param and the slash in front of it.
+/// Parentheses that are not closed with `)?` are literal, e.g. in `/wiki/Foo_(bar)`.
+/// Expansions with an optional part present come before the ones without it. An expansion that
+/// would be empty (like the one of `/:lang?` without the param) keeps its slash instead.
+fn expand_optional_segments(pattern: &str) -> Result<Vec<String>, &'static str> {
+    if !pattern.contains(['(', ')', '?']) {
+        return Ok(vec![pattern.to_string()]);
+    }
+
+    let (expansions, _) = expand_group(pattern, false, None)?;
+    let mut paths: Vec<String> = Vec::with_capacity(expansions.len());
+    for mut path in expansions {
+        if path.is_empty() {
+            path.push('/');
+        }
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Expands `pattern` up to the end of the current group (the first unbalanced `)` if
+/// `is_nested`) and returns the expansions along with the rest of the pattern. `prev` is the
+/// character in front of the `pattern`.
+fn expand_group(
+    pattern: &str,
+    is_nested: bool,
+    mut prev: Option<char>,
+) -> Result<(Vec<String>, &str), &'static str> {
+    let mut paths = vec![String::new()];
+    let mut rest = pattern;
+    // number of currently open literal parentheses, whose `)` is literal too
+    let mut literal_parens = 0;
+    while let Some(ch) = rest.chars().next() {
+        match ch {
+            ':' if is_param_start(prev, rest) => {
+                let (_, _, remaining) = extract_param(rest)?;
+                let param = &rest[..rest.len() - remaining.len()];
+                if let Some(remaining) = remaining.strip_prefix('?') {
+                    if !remaining.is_empty() && !remaining.starts_with(['/', '.', '(', ')']) {
+                        return Err("optional parameter must end the segment");
+                    }
+                    let with_param = paths.iter().map(|path| format!("{}{}", path, param));
+                    let without_param = paths.iter().map(|path| {
+                        let mut path = path.clone();
+                        if path.ends_with('/') {
+                            path.pop();
+                        }
+                        path
+                    });
+                    paths = with_param.chain(without_param).collect();
+                    rest = remaining;
+                } else {
+                    for path in &mut paths {
+                        path.push_str(param);
+                    }
+                    rest = remaining;
+                }
+                prev = param.chars().next_back();
+                continue;
+            }
+            '(' if !is_optional_group(rest)? => literal_parens += 1,
+            ')' if literal_parens > 0 => literal_parens -= 1,
+            '(' => {
+                let (group, remaining) = expand_group(&rest[1..], true, prev)?;
+                paths = paths
+                    .iter()
+                    .flat_map(|path| {
+                        group
+                            .iter()
+                            .map(move |part| format!("{}{}", path, part))
+                            .chain(Some(path.clone()))
+                    })
+                    .collect();
+                rest = &remaining[2..];
+                // only keep track of the previous character if it is the same for all expansions
+                let mut last_chars = paths.iter().map(|path| path.chars().next_back());
+                let first = last_chars.next().and_then(|ch| ch);
+                prev = if last_chars.all(|ch| ch == first) {
+                    first
+                } else {
+                    None
+                };
+                continue;
+            }
+            ')' if is_nested => return Ok((paths, rest)),
+            _ => {}
+        }
+        for path in &mut paths {
+            path.push(ch);
+        }
+        prev = Some(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    Ok((paths, rest))
+}
+
+/// Whether the `(` at the start of `pattern` opens an optional group, i.e. whether its matching
+/// `)` is followed by a `?`. Parentheses inside of param constraints are ignored.
+fn is_optional_group(pattern: &str) -> Result<bool, &'static str> {
+    let mut depth = 0;
+    let mut rest = pattern;
+    let mut prev = None;
+    while let Some(ch) = rest.chars().next() {
+        match ch {
+            ':' if is_param_start(prev, rest) => {
+                let (_, _, remaining) = extract_param(rest)?;
+                prev = rest[..rest.len() - remaining.len()].chars().next_back();
+                rest = remaining;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(rest[1..].starts_with('?'));
+                }
+            }
+            _ => {}
+        }
+        prev = Some(ch);
+        rest = &rest[ch.len_utf8()..];
     }
+    Ok(false)
 }
 
-fn extract_param_name(path: &str) -> (&str, &str) {
+/// Builds a path from the given route `pattern` by replacing its params with the percent-encoded
+/// values from `params`. Optional segments are included if all their params are provided.
+pub fn build_path(pattern: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+    for (i, &(name, _)) in params.iter().enumerate() {
+        if params[..i].iter().any(|&(n, _)| n == name) {
+            return Err(UrlError::DuplicateParam {
+                name: name.to_string(),
+            });
+        }
+    }
+
+    let paths = expand_optional_segments(pattern).expect("pattern has been validated");
+    let mut first_err = None;
+    for path in &paths {
+        match build_concrete_path(path, params) {
+            Ok(path) => return Ok(path),
+            Err(err) => {
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_err.expect("patterns expand to at least one path"))
+}
+
+fn build_concrete_path(pattern: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+    let mut path = String::with_capacity(pattern.len());
+    let mut used = vec![false; params.len()];
+    let mut rest = pattern;
+    let mut prev = None;
+    while let Some(ch) = rest.chars().next() {
+        if is_param_start(prev, rest) {
+            let (name, constraint, remaining) = if ch == ':' {
+                extract_param(rest).expect("pattern has been validated")
+            } else {
+                (&rest[1..], None, "")
+            };
+            let i = params.iter().position(|&(n, _)| n == name).ok_or_else(|| {
+                UrlError::MissingParam {
+                    name: name.to_string(),
+                }
+            })?;
+            used[i] = true;
+            // the built path has to match the route, which only captures empty catch-alls
+            let value = params[i].1;
+            let is_valid = (!value.is_empty() || ch == '*')
+                && constraint.is_none_or(|constraint| {
+                    Constraint::parse(constraint)
+                        .expect("pattern has been validated")
+                        .matches(value)
+                });
+            if !is_valid {
+                return Err(UrlError::InvalidParam {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                });
+            }
+            // the literal following the param has to be encoded too, to not end it early
+            let separator = remaining.chars().next().filter(|&ch| ch != '/');
+            percent_encode_into(&mut path, value, ch == '*', separator);
+            prev = rest[..rest.len() - remaining.len()].chars().next_back();
+            rest = remaining;
+            continue;
+        }
+        path.push(ch);
+        prev = Some(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    if let Some(i) = used.iter().position(|used| !used) {
+        return Err(UrlError::ExtraParam {
+            name: params[i].0.to_string(),
+        });
+    }
+
+    Ok(path)
+}
+
+/// Lowercases the static parts of the (already validated) `path` and strips the names of its
+/// params, so that paths that would match the same requests when ignoring case are equal.
+fn fold_case(path: &str) -> String {
+    let mut folded = String::with_capacity(path.len());
+    let mut rest = path;
+    let mut prev = None;
+    while let Some(ch) = rest.chars().next() {
+        if is_param_start(prev, rest) {
+            let remaining = if ch == ':' {
+                let (_, constraint, remaining) =
+                    extract_param(rest).expect("pattern has been validated");
+                folded.push(':');
+                if let Some(constraint) = constraint {
+                    folded.push('<');
+                    folded.push_str(constraint);
+                    folded.push('>');
+                }
+                remaining
+            } else {
+                folded.push('*');
+                ""
+            };
+            prev = rest[..rest.len() - remaining.len()].chars().next_back();
+            rest = remaining;
+            continue;
+        }
+        folded.extend(ch.to_lowercase());
+        prev = Some(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    folded
+}
+
+/// Splits a `:name<constraint>` param off the front of `path` and returns its name, its
+/// constraint (if any) and the remaining path. The constraint may contain delimiters like `/` or
+/// `.` as long as its angle brackets are balanced (or escaped).
+fn extract_param(path: &str) -> Result<(&str, Option<&str>, &str), &'static str> {
     let (colon, path) = path.split_at(1);
     assert_eq!(colon, ":");
 
-    split_at_next_param_delimiter(path)
-}
+    let split_at = path.find(|ch| !is_name_char(ch)).unwrap_or(path.len());
+    let (name, rest) = path.split_at(split_at);
+    if !rest.starts_with('<') {
+        return Ok((name, None, rest));
+    }
 
-fn split_at_next_param_delimiter(path: &str) -> (&str, &str) {
-    let split_at = {
-        let mut i = 0;
-        for ch in path.chars() {
-            if ch == '/' || ch == '.' {
-                break;
+    let mut depth = 0;
+    let mut is_escaped = false;
+    for (i, ch) in rest.char_indices() {
+        match ch {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((name, Some(&rest[1..i]), &rest[i + 1..]));
+                }
             }
-            i += 1;
+            _ => {}
+        }
+    }
+    Err("unclosed parameter constraint")
+}
+
+/// Strips `prefix` off `path`, comparing characters case-insensitively if `ignore_case` is set.
+/// Non-ASCII characters match their percent-encoded form too (see `next_char`).
+fn strip_prefix<'a>(path: &'a str, prefix: &str, ignore_case: bool) -> Option<&'a str> {
+    if let Some(rest) = path.strip_prefix(prefix) {
+        return Some(rest);
+    }
+
+    let (mut rest, mut prefix) = (path, prefix);
+    while let Some((expected, expected_len)) = next_char(prefix) {
+        let (ch, len) = next_char(rest)?;
+        if ch != expected && !(ignore_case && eq_ignore_case(ch, expected)) {
+            return None;
+        }
+        rest = &rest[len..];
+        prefix = &prefix[expected_len..];
+    }
+    Some(rest)
+}
+
+/// Returns the first character of `path` along with its length in `path`. Non-ASCII characters
+/// are also read from their percent-encoded form (e.g. `%C3%A9` for `é`), since that is how they
+/// are part of a URI. Percent-encoded ASCII characters are kept as they are, so that e.g. `%2F`
+/// does not end a segment.
+fn next_char(path: &str) -> Option<(char, usize)> {
+    let ch = path.chars().next()?;
+    if ch == '%' {
+        if let Some(decoded) = decode_non_ascii(path) {
+            return Some(decoded);
         }
-        i
+    }
+    Some((ch, ch.len_utf8()))
+}
+
+fn decode_non_ascii(path: &str) -> Option<(char, usize)> {
+    let encoded = path.as_bytes();
+    let byte_at = |i: usize| match *encoded.get(i * 3..i * 3 + 3)? {
+        [b'%', hi, lo] => Some(hex_value(hi)? << 4 | hex_value(lo)?),
+        _ => None,
     };
-    path.split_at(split_at)
+    let len = match byte_at(0)? {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let mut bytes = [0; 4];
+    for (i, b) in bytes[..len].iter_mut().enumerate() {
+        *b = byte_at(i)?;
+    }
+    let ch = str::from_utf8(&bytes[..len]).ok()?.chars().next()?;
+    Some((ch, len * 3))
+}
+
+/// Like `str::char_indices`, but reads percent-encoded non-ASCII characters (see `next_char`).
+fn char_indices(path: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut i = 0;
+    iter::from_fn(move || {
+        let (ch, len) = next_char(&path[i..])?;
+        i += len;
+        Some((i - len, ch))
+    })
+}
+
+fn eq_ignore_case(lhs: char, rhs: char) -> bool {
+    lhs == rhs || lhs.to_lowercase().eq(rhs.to_lowercase())
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Whether `rest` starts with a param (`:name`) or a catch-all (`*name`), given the character in
+/// front of it. Params either start a segment or follow a literal that cannot be part of a name,
+/// e.g. the `-` in `/:from-:to`. Catch-alls always start a segment.
+fn is_param_start(prev: Option<char>, rest: &str) -> bool {
+    let mut chars = rest.chars();
+    match (prev, chars.next()) {
+        (Some('/'), Some(':')) | (Some('/'), Some('*')) => true,
+        (Some(prev), Some(':')) => !is_name_char(prev) && chars.next().is_some_and(is_name_char),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use error::{RouteError, UrlError};
+    use params::Params;
+    use quickcheck::{quickcheck, TestResult};
+    use std::collections::BTreeSet;
     use std::collections::HashMap;
-    use tree::{Node, Param, Tree};
+    use tree::{build_path, CatchAll, Node, Param, Tree};
 
     #[test]
     fn root_branch() {
         let mut tree = Tree::new();
-        tree.add_path("a", 1);
-        tree.add_path("b", 2);
+        tree.add_path("a", 0);
+        tree.add_path("b", 1);
 
         let mut childs = HashMap::new();
         childs.insert(
             'a',
             Node {
-                path: "a",
-                value: Some(1),
+                path: "a".to_string(),
+                value: Some(0),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         childs.insert(
             'b',
             Node {
-                path: "b",
-                value: Some(2),
+                path: "b".to_string(),
+                value: Some(1),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "",
+                path: "".to_string(),
                 value: None,
-                childs: childs,
-                wildcard: None,
+                childs,
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
     }
@@ -270,93 +1110,102 @@ mod tests {
     fn branch() {
         let mut tree = Tree::new();
 
-        tree.add_path("/foobar", 1);
+        tree.add_path("/foobar", 0);
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "/foobar",
-                value: Some(1),
+                path: "/foobar".to_string(),
+                value: Some(0),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
 
-        tree.add_path("/foocar", 2);
+        tree.add_path("/foocar", 1);
         let mut childs = HashMap::new();
         childs.insert(
             'b',
             Node {
-                path: "bar",
-                value: Some(1),
+                path: "bar".to_string(),
+                value: Some(0),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         childs.insert(
             'c',
             Node {
-                path: "car",
-                value: Some(2),
+                path: "car".to_string(),
+                value: Some(1),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "/foo",
+                path: "/foo".to_string(),
                 value: None,
-                childs: childs,
-                wildcard: None,
+                childs,
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
 
-        tree.add_path("/otherwise", 3);
+        tree.add_path("/otherwise", 2);
         let mut subchilds = HashMap::new();
         subchilds.insert(
             'b',
             Node {
-                path: "bar",
-                value: Some(1),
+                path: "bar".to_string(),
+                value: Some(0),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         subchilds.insert(
             'c',
             Node {
-                path: "car",
-                value: Some(2),
+                path: "car".to_string(),
+                value: Some(1),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         let mut childs = HashMap::new();
         childs.insert(
             'f',
             Node {
-                path: "foo",
+                path: "foo".to_string(),
                 value: None,
                 childs: subchilds,
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         childs.insert(
             'o',
             Node {
-                path: "otherwise",
-                value: Some(3),
+                path: "otherwise".to_string(),
+                value: Some(2),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "/",
+                path: "/".to_string(),
                 value: None,
-                childs: childs,
-                wildcard: None,
+                childs,
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
     }
@@ -365,35 +1214,162 @@ mod tests {
     fn append() {
         let mut tree = Tree::new();
 
-        tree.add_path("/foo", 1);
+        tree.add_path("/foo", 0);
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "/foo",
-                value: Some(1),
+                path: "/foo".to_string(),
+                value: Some(0),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
 
-        tree.add_path("/foobar", 2);
+        tree.add_path("/foobar", 1);
         let mut childs = HashMap::new();
         childs.insert(
             'b',
             Node {
-                path: "bar",
-                value: Some(2),
+                path: "bar".to_string(),
+                value: Some(1),
                 childs: HashMap::new(),
-                wildcard: None,
+                wildcards: Vec::new(),
+                catch_all: None,
             },
         );
         assert_eq!(
-            tree.0,
+            tree.root,
             Some(Node {
-                path: "/foo",
+                path: "/foo".to_string(),
+                value: Some(0),
+                childs,
+                wildcards: Vec::new(),
+                catch_all: None,
+            })
+        );
+    }
+
+    #[test]
+    fn remove_merges_nodes() {
+        let mut tree = Tree::new();
+        tree.add_path("/foobar", 0);
+        tree.add_path("/foocar", 1);
+        tree.add_path("/otherwise", 2);
+
+        assert_eq!(tree.remove("/otherwise"), Some(2));
+        let mut childs = HashMap::new();
+        childs.insert(
+            'b',
+            Node {
+                path: "bar".to_string(),
+                value: Some(0),
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
+            },
+        );
+        childs.insert(
+            'c',
+            Node {
+                path: "car".to_string(),
                 value: Some(1),
-                childs: childs,
-                wildcard: None,
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
+            },
+        );
+        assert_eq!(
+            tree.root,
+            Some(Node {
+                path: "/foo".to_string(),
+                value: None,
+                childs,
+                wildcards: Vec::new(),
+                catch_all: None,
+            })
+        );
+
+        // the index of `/foocar` is updated after `/foobar` has been removed
+        assert_eq!(tree.remove("/foobar"), Some(0));
+        assert_eq!(
+            tree.root,
+            Some(Node {
+                path: "/foocar".to_string(),
+                value: Some(0),
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
+            })
+        );
+        assert_eq!(tree.find_test("/foocar"), Some(&1));
+
+        assert_eq!(tree.remove("/foocar"), Some(1));
+        assert_eq!(tree.root, None);
+        assert_eq!(tree.remove("/foocar"), None);
+    }
+
+    #[test]
+    fn remove_keeps_intermediate_values() {
+        let mut tree = Tree::new();
+        tree.add_path("/foo", 0);
+        tree.add_path("/foobar", 1);
+
+        assert_eq!(tree.remove("/foobar"), Some(1));
+        assert_eq!(
+            tree.root,
+            Some(Node {
+                path: "/foo".to_string(),
+                value: Some(0),
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
+            })
+        );
+    }
+
+    #[test]
+    fn remove_params() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/new", 0);
+        tree.add_path("/users/:id/posts", 1);
+        tree.add_path("/users/:id<int>", 2);
+        tree.add_path("/users/*path", 3);
+
+        assert_eq!(tree.remove("/users/:id"), None);
+        assert_eq!(tree.remove("/users/:id<int>"), Some(2));
+        assert_eq!(tree.find_test("/users/1"), Some(&3));
+        assert_eq!(tree.find_test("/users/1/posts"), Some(&1));
+        assert_eq!(tree.remove("/users/*path"), Some(3));
+        assert_eq!(tree.remove("/users/:id/posts"), Some(1));
+        assert_eq!(
+            tree.root,
+            Some(Node {
+                path: "/users/new".to_string(),
+                value: Some(0),
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
+            })
+        );
+    }
+
+    #[test]
+    fn remove_optional_segments() {
+        let mut tree = Tree::new();
+        tree.add_path("/posts", 0);
+        tree.add_path("/posts/:page/comments(/:comment)?", 1);
+
+        assert_eq!(tree.remove("/posts/:page/comments/:comment"), None);
+        assert_eq!(tree.remove("/posts/:page/comments(/:comment)?"), Some(1));
+        assert_eq!(
+            tree.root,
+            Some(Node {
+                path: "/posts".to_string(),
+                value: Some(0),
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: None,
             })
         );
     }
@@ -405,18 +1381,21 @@ mod tests {
         assert_eq!(
             node,
             Node {
-                path: "/foo/",
+                path: "/foo/".to_string(),
                 value: None,
                 childs: HashMap::new(),
-                wildcard: Some(Param {
-                    name: "bar",
+                wildcards: vec![Param {
+                    name: "bar".to_string(),
+                    constraint: None,
                     node: Box::new(Node {
-                        path: "/more",
+                        path: "/more".to_string(),
                         value: Some(1),
                         childs: HashMap::new(),
-                        wildcard: None,
+                        wildcards: Vec::new(),
+                        catch_all: None,
                     }),
-                }),
+                }],
+                catch_all: None,
             }
         );
     }
@@ -434,8 +1413,8 @@ mod tests {
     fn param_special_character() {
         let mut tree = Tree::new();
         tree.add_path("/foo/:id.json", 1);
-        let mut params = HashMap::new();
-        params.insert("id".to_string(), "42".to_string());
+        let mut params = Params::new();
+        params.push("id", "42");
         assert_eq!(tree.find("/foo/42.json"), Some((&1, params)));
     }
 
@@ -471,9 +1450,848 @@ mod tests {
     fn params() {
         let mut tree = Tree::new();
         tree.add_path("/a/:a/b/:b", 1);
-        let mut params = HashMap::new();
-        params.insert("a".to_string(), "12".to_string());
-        params.insert("b".to_string(), "345".to_string());
+        let mut params = Params::new();
+        params.push("a", "12");
+        params.push("b", "345");
         assert_eq!(tree.find("/a/12/b/345"), Some((&1, params)));
     }
+
+    #[test]
+    fn catch_all() {
+        let node = Node::new("/static/*path", Some(1));
+
+        assert_eq!(
+            node,
+            Node {
+                path: "/static/".to_string(),
+                value: None,
+                childs: HashMap::new(),
+                wildcards: Vec::new(),
+                catch_all: Some(CatchAll {
+                    name: "path".to_string(),
+                    value: Some(1),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn catch_all_find() {
+        let mut tree = Tree::new();
+        tree.add_path("/static/*path", 1);
+        assert_eq!(tree.find_test("/static/app.js"), Some(&1));
+        assert_eq!(tree.find_test("/static/js/app.js"), Some(&1));
+        assert_eq!(tree.find_test("/static/"), Some(&1));
+        assert_eq!(tree.find_test("/static"), None);
+
+        tree.add_path("/proxy/:host/*upstream", 2);
+        assert_eq!(tree.find_test("/proxy/backend/a/b"), Some(&2));
+        assert_eq!(tree.find_test("/proxy/backend"), None);
+    }
+
+    #[test]
+    fn catch_all_precedence() {
+        let mut tree = Tree::new();
+        tree.add_path("/files/*path", 1);
+        tree.add_path("/files/index.html", 2);
+        tree.add_path("/files/:name/raw", 3);
+
+        assert_eq!(tree.find_test("/files/index.html"), Some(&2));
+        assert_eq!(tree.find_test("/files/index.htm"), Some(&1));
+        assert_eq!(tree.find_test("/files/index.html/more"), Some(&1));
+        assert_eq!(tree.find_test("/files/other/raw"), Some(&3));
+        assert_eq!(tree.find_test("/files/other/raw/more"), Some(&1));
+        assert_eq!(tree.find_test("/files/other"), Some(&1));
+    }
+
+    #[test]
+    fn catch_all_params() {
+        let mut tree = Tree::new();
+        tree.add_path("/proxy/:host/*upstream", 1);
+        let mut params = Params::new();
+        params.push("host", "backend");
+        params.push("upstream", "api/v1/users");
+        assert_eq!(tree.find("/proxy/backend/api/v1/users"), Some((&1, params)));
+    }
+
+    #[test]
+    fn backtrack_static_to_param() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/new", 1);
+        tree.add_path("/users/:id", 2);
+        assert_eq!(tree.find_test("/users/new"), Some(&1));
+        assert_eq!(tree.find_test("/users/nobody"), Some(&2));
+        assert_eq!(tree.find_test("/users/newer"), Some(&2));
+        assert_eq!(tree.find_test("/users/ne"), Some(&2));
+        assert_eq!(tree.find_test("/users/"), None);
+    }
+
+    #[test]
+    fn backtrack_nested() {
+        let mut tree = Tree::new();
+        tree.add_path("/a/b/c", 1);
+        tree.add_path("/a/:x/d", 2);
+        tree.add_path("/a/:x/:y/e", 3);
+        assert_eq!(tree.find_test("/a/b/c"), Some(&1));
+        assert_eq!(tree.find_test("/a/b/d"), Some(&2));
+        assert_eq!(tree.find_test("/a/b/c/e"), Some(&3));
+        assert_eq!(tree.find_test("/a/b/x"), None);
+
+        let mut params = Params::new();
+        params.push("x", "b");
+        params.push("y", "c");
+        assert_eq!(tree.find("/a/b/c/e"), Some((&3, params)));
+    }
+
+    #[test]
+    fn backtrack_to_catch_all() {
+        let mut tree = Tree::new();
+        tree.add_path("/x/static", 1);
+        tree.add_path("/x/:a/y", 2);
+        tree.add_path("/x/*rest", 3);
+        assert_eq!(tree.find_test("/x/static"), Some(&1));
+        assert_eq!(tree.find_test("/x/1/y"), Some(&2));
+        assert_eq!(tree.find_test("/x/static/y"), Some(&2));
+
+        // params of failed branches must not leak into the result
+        let mut params = Params::new();
+        params.push("rest", "1/z");
+        assert_eq!(tree.find("/x/1/z"), Some((&3, params)));
+    }
+
+    #[test]
+    fn priority_is_independent_of_insertion_order() {
+        let routes = [("/p/new", 1), ("/p/:id", 2), ("/p/*rest", 3)];
+        let requests = [
+            ("/p/new", Some(&1)),
+            ("/p/old", Some(&2)),
+            ("/p/new/old", Some(&3)),
+        ];
+
+        for order in &[[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let mut tree = Tree::new();
+            for &i in order {
+                tree.add_path(routes[i].0, routes[i].1);
+            }
+            for &(path, expected) in &requests {
+                assert_eq!(tree.find_test(path), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_route() {
+        let mut tree = Tree::new();
+        tree.add_path("/foo", 1);
+        tree.add_path("/foo/:id", 2);
+        tree.add_path("/foo/*rest", 3);
+        assert_eq!(
+            tree.try_add_path("/foo", 4),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/foo".to_string()
+            })
+        );
+        assert_eq!(
+            tree.try_add_path("/foo/:id", 5),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/foo/:id".to_string()
+            })
+        );
+        assert_eq!(
+            tree.try_add_path("/foo/*rest", 6),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/foo/*rest".to_string()
+            })
+        );
+        assert_eq!(tree.find_test("/foo"), Some(&1));
+        assert_eq!(tree.find_test("/foo/42"), Some(&2));
+        assert_eq!(tree.find_test("/foo/4/2"), Some(&3));
+    }
+
+    #[test]
+    fn add_path_to_intermediate_node() {
+        let mut tree = Tree::new();
+        tree.add_path("/foobar", 1);
+        tree.add_path("/foocar", 2);
+        assert_eq!(tree.try_add_path("/foo", 3), Ok(()));
+        assert_eq!(tree.find_test("/foo"), Some(&3));
+    }
+
+    #[test]
+    fn conflicting_param_names() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/:id", 1);
+        assert_eq!(
+            tree.try_add_path("/users/:name/posts", 2),
+            Err(RouteError::ConflictingParamNames {
+                pattern: "/users/:name/posts".to_string(),
+                existing: "id".to_string(),
+                name: "name".to_string(),
+            })
+        );
+
+        tree.add_path("/files/*path", 1);
+        assert_eq!(
+            tree.try_add_path("/files/*rest", 2),
+            Err(RouteError::ConflictingParamNames {
+                pattern: "/files/*rest".to_string(),
+                existing: "path".to_string(),
+                name: "rest".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot have different parameter names at the same position")]
+    fn conflicting_param_names_panic() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/:id", 1);
+        tree.add_path("/users/:name", 2);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let mut tree = Tree::new();
+        for &(pattern, reason) in &[
+            ("/users/:", "parameter without a name"),
+            ("/users/:/posts", "parameter without a name"),
+            ("/users/:.json", "parameter without a name"),
+            ("/files/*", "catch-all without a name"),
+            ("/files/*path/raw", "catch-all must be the last segment"),
+            ("/files/*path?", "invalid catch-all name"),
+            ("/files/*a(b)", "invalid catch-all name"),
+            ("/files/*path.json", "invalid catch-all name"),
+            ("/users/:id/posts/:id", "duplicate parameter name"),
+            ("/users/:id/*id", "duplicate parameter name"),
+            ("/users/:id<int", "unclosed parameter constraint"),
+            ("/users/:id<>", "empty parameter constraint"),
+            (
+                "/users/:id<int>x",
+                "parameter constraint must end the parameter",
+            ),
+        ] {
+            assert_eq!(
+                tree.try_add_path(pattern, 1),
+                Err(RouteError::InvalidPattern {
+                    pattern: pattern.to_string(),
+                    reason,
+                })
+            );
+        }
+        assert_eq!(tree.root, None);
+    }
+
+    #[test]
+    fn constraints() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/:name", 1);
+        tree.add_path("/users/:id<int>", 2);
+        tree.add_path("/users/:id<uuid>/posts", 3);
+        tree.add_path("/users/me", 4);
+        tree.add_path("/users/:id<int>/*path", 5);
+
+        assert_eq!(tree.find_test("/users/42"), Some(&2));
+        assert_eq!(tree.find_test("/users/john"), Some(&1));
+        assert_eq!(tree.find_test("/users/me"), Some(&4));
+        assert_eq!(
+            tree.find_test("/users/67e55044-10b1-426f-9247-bb680e5fe0c8/posts"),
+            Some(&3)
+        );
+        assert_eq!(tree.find_test("/users/42/posts"), Some(&5));
+        assert_eq!(tree.find_test("/users/john/posts"), None);
+
+        let (_, params) = tree.find("/users/42").unwrap();
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.len(), 1);
+    }
+
+    #[test]
+    fn constraints_fall_through() {
+        let mut tree = Tree::new();
+        tree.add_path("/files/:id<int>", 1);
+        tree.add_path("/files/*path", 2);
+
+        assert_eq!(tree.find_test("/files/1"), Some(&1));
+        let (value, params) = tree.find("/files/one").unwrap();
+        assert_eq!(*value, 2);
+        assert_eq!(params.get("id"), None);
+        assert_eq!(params.get("path"), Some("one"));
+
+        // constraints are checked against the percent-decoded value
+        let mut tree = Tree::new();
+        tree.add_path("/:name<alpha>", 1);
+        assert_eq!(tree.find_test("/caf%C3%A9"), Some(&1));
+        assert_eq!(tree.find_test("/caf%20"), None);
+    }
+
+    #[test]
+    fn constraints_conflicting_param_names() {
+        let mut tree = Tree::new();
+        tree.add_path("/users/:id<int>", 1);
+        assert_eq!(
+            tree.try_add_path("/users/:user_id<int>/posts", 2),
+            Err(RouteError::ConflictingParamNames {
+                pattern: "/users/:user_id<int>/posts".to_string(),
+                existing: "id".to_string(),
+                name: "user_id".to_string(),
+            })
+        );
+        assert_eq!(
+            tree.try_add_path("/users/:id<int>", 3),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/users/:id<int>".to_string(),
+            })
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_constraints() {
+        let mut tree = Tree::new();
+        tree.add_path("/posts/:slug<re:[a-z0-9]+(?:-[a-z0-9]+)*>", 1);
+        tree.add_path("/posts/:year<re:\\d{4}>/:slug", 2);
+        tree.add_path("/posts/:title", 3);
+
+        assert_eq!(tree.find_test("/posts/hello-world"), Some(&1));
+        assert_eq!(tree.find_test("/posts/Hello-World"), Some(&3));
+        assert_eq!(tree.find_test("/posts/2018/hello"), Some(&2));
+        assert_eq!(tree.find_test("/posts/18/hello"), None);
+        tree.add_path("/tags/:tag<re:(a|b)+>(/:page)?", 4);
+        assert_eq!(tree.find_test("/tags/abba"), Some(&4));
+        assert_eq!(tree.find_test("/tags/abba/2"), Some(&4));
+        assert_eq!(
+            tree.try_add_path("/posts/:slug<re:[a-z>", 4),
+            Err(RouteError::InvalidPattern {
+                pattern: "/posts/:slug<re:[a-z>".to_string(),
+                reason: "invalid regex constraint",
+            })
+        );
+        assert_eq!(
+            tree.try_add_path("/posts/:id<itn>", 4),
+            Err(RouteError::InvalidPattern {
+                pattern: "/posts/:id<itn>".to_string(),
+                reason: "unknown parameter constraint",
+            })
+        );
+    }
+
+    #[test]
+    fn multiple_params_per_segment() {
+        let mut tree = Tree::new();
+        tree.add_path("/pkg/:name@:version", 1);
+        tree.add_path("/range/:from-:to", 2);
+        tree.add_path("/archive/:year::month", 3);
+        tree.add_path("/files/:name.:ext", 4);
+        tree.add_path("/files/:name", 5);
+
+        let (value, params) = tree.find("/pkg/router@0.1.0").unwrap();
+        assert_eq!(*value, 1);
+        assert_eq!(params.get("name"), Some("router"));
+        assert_eq!(params.get("version"), Some("0.1.0"));
+        assert_eq!(tree.find_test("/pkg/router"), None);
+        assert_eq!(tree.find_test("/pkg/@0.1.0"), None);
+
+        let (_, params) = tree.find("/range/2018-10").unwrap();
+        assert_eq!(params.get("from"), Some("2018"));
+        assert_eq!(params.get("to"), Some("10"));
+        let (_, params) = tree.find("/archive/2018:10").unwrap();
+        assert_eq!(params.get("year"), Some("2018"));
+        assert_eq!(params.get("month"), Some("10"));
+
+        // the value is matched up to the next literal, but is extended if the rest does not match
+        let (value, params) = tree.find("/files/app.min.js").unwrap();
+        assert_eq!(*value, 4);
+        assert_eq!(params.get("name"), Some("app"));
+        assert_eq!(params.get("ext"), Some("min.js"));
+        let (value, params) = tree.find("/files/app").unwrap();
+        assert_eq!(*value, 5);
+        assert_eq!(params.get("name"), Some("app"));
+        assert_eq!(params.len(), 1);
+    }
+
+    #[test]
+    fn multiple_params_per_segment_backtrack() {
+        let mut tree = Tree::new();
+        tree.add_path("/:name.json", 1);
+        tree.add_path("/:name-:version<int>", 2);
+
+        let (_, params) = tree.find("/v1.2.json").unwrap();
+        assert_eq!(params.get("name"), Some("v1.2"));
+        let (_, params) = tree.find("/a-b-3").unwrap();
+        assert_eq!(params.get("name"), Some("a-b"));
+        assert_eq!(params.get("version"), Some("3"));
+        assert_eq!(tree.find_test("/a-b"), None);
+
+        assert_eq!(
+            tree.try_add_path("/:a<int>:b", 3),
+            Err(RouteError::InvalidPattern {
+                pattern: "/:a<int>:b".to_string(),
+                reason: "parameters must be separated by a literal",
+            })
+        );
+    }
+
+    #[test]
+    fn optional_segments() {
+        let mut tree = Tree::new();
+        tree.add_path("/posts(/:page)?", 1);
+        tree.add_path("/docs/:lang?", 2);
+        tree.add_path("/archive(/:year<int>(/:month<int>)?)?", 3);
+        tree.add_path("/files/:name(.json)?", 4);
+
+        assert_eq!(tree.find_test("/posts"), Some(&1));
+        assert_eq!(tree.find_test("/posts/2"), Some(&1));
+        assert_eq!(tree.find_test("/posts/"), None);
+        assert_eq!(tree.find_test("/docs"), Some(&2));
+        assert_eq!(tree.find_test("/docs/de"), Some(&2));
+        assert_eq!(tree.find_test("/archive"), Some(&3));
+        assert_eq!(tree.find_test("/archive/2018/10"), Some(&3));
+        assert_eq!(tree.find_test("/archive/latest"), None);
+        assert_eq!(tree.find_test("/files/readme.json"), Some(&4));
+
+        let (_, params) = tree.find("/posts").unwrap();
+        assert!(params.is_empty());
+        let (_, params) = tree.find("/posts/2").unwrap();
+        assert_eq!(params.get("page"), Some("2"));
+        let (_, params) = tree.find("/archive/2018").unwrap();
+        assert_eq!(params.get("year"), Some("2018"));
+        assert_eq!(params.get("month"), None);
+        let (_, params) = tree.find("/files/readme").unwrap();
+        assert_eq!(params.get("name"), Some("readme"));
+    }
+
+    #[test]
+    fn optional_root_segment() {
+        let mut tree = Tree::new();
+        tree.add_path("/:lang?", 1);
+
+        assert_eq!(tree.find_test("/"), Some(&1));
+        assert_eq!(tree.find_test("/en"), Some(&1));
+        assert_eq!(tree.find_test(""), None);
+        let (_, params) = tree.find("/en").unwrap();
+        assert_eq!(params.get("lang"), Some("en"));
+        assert_eq!(build_path("/:lang?", &[]), Ok("/".to_string()));
+        assert_eq!(
+            build_path("/:lang?", &[("lang", "en")]),
+            Ok("/en".to_string())
+        );
+
+        let mut tree = Tree::new();
+        tree.add_path("(/:lang)?", 1);
+        assert_eq!(tree.find_test("/"), Some(&1));
+        assert_eq!(tree.find_test("/en"), Some(&1));
+        assert_eq!(build_path("(/:lang)?", &[]), Ok("/".to_string()));
+    }
+
+    #[test]
+    fn literal_parentheses() {
+        let mut tree = Tree::new();
+        tree.add_path("/wiki/Foo_(bar)", 1);
+        tree.add_path("/wiki/:title(-(disambiguation))?", 2);
+        tree.add_path("/files/:name(1)", 3);
+        tree.add_path("/faq/why)", 5);
+
+        assert_eq!(tree.find_test("/wiki/Foo_(bar)"), Some(&1));
+        assert_eq!(tree.find_test("/wiki/Foo"), Some(&2));
+        assert_eq!(tree.find_test("/wiki/Foo-(disambiguation)"), Some(&2));
+        assert_eq!(tree.find_test("/files/readme(1)"), Some(&3));
+        assert_eq!(tree.find_test("/faq/why)"), Some(&5));
+        assert_eq!(
+            build_path("/wiki/Foo_(bar)", &[]),
+            Ok("/wiki/Foo_(bar)".to_string())
+        );
+        assert_eq!(
+            build_path("/wiki/:title(-(disambiguation))?", &[("title", "Foo")]),
+            Ok("/wiki/Foo-(disambiguation)".to_string())
+        );
+    }
+
+    #[test]
+    fn optional_segments_conflict() {
+        let mut tree = Tree::new();
+        tree.add_path("/posts", 1);
+        assert_eq!(
+            tree.try_add_path("/posts/:id?", 2),
+            Err(RouteError::DuplicateRoute {
+                pattern: "/posts/:id?".to_string(),
+            })
+        );
+        // the already added expansion `/posts/:id` has been removed again
+        assert_eq!(tree.find_test("/posts/1"), None);
+        assert_eq!(tree.find_test("/posts"), Some(&1));
+
+        for &(pattern, reason) in &[
+            ("/posts/:page?x", "optional parameter must end the segment"),
+            ("/posts(/:)?", "parameter without a name"),
+        ] {
+            assert_eq!(
+                tree.try_add_path(pattern, 3),
+                Err(RouteError::InvalidPattern {
+                    pattern: pattern.to_string(),
+                    reason,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn ignore_case() {
+        let mut tree = Tree::new();
+        tree.add_path("/Users/:name/Posts", 1);
+        tree.add_path("/users/new", 2);
+        tree.add_path("/café/*path", 3);
+        tree.add_path("/pkg/:name@:version", 4);
+        assert_eq!(tree.find_test("/users/John/posts"), None);
+
+        tree.set_ignore_case(true);
+        let (value, params) = tree.find("/USERS/John/posts").unwrap();
+        assert_eq!(*value, 1);
+        assert_eq!(params.get("name"), Some("John"));
+        assert_eq!(tree.find_test("/users/new"), Some(&2));
+        assert_eq!(tree.find_test("/Users/NEW"), Some(&2));
+        assert_eq!(tree.find_test("/users/newer"), None);
+
+        let (value, params) = tree.find("/CAFÉ/Index.HTML").unwrap();
+        assert_eq!(*value, 3);
+        assert_eq!(params.get("path"), Some("Index.HTML"));
+
+        let mut params = Params::new();
+        params.push("name", "A");
+        params.push("version", "B");
+        assert_eq!(tree.find("/PKG/A@B"), Some((&4, params)));
+    }
+
+    #[test]
+    fn ignore_case_duplicates() {
+        let mut tree = Tree::new();
+        tree.set_ignore_case(true);
+        tree.add_path("/Users", 1);
+        tree.add_path("/users/:id<int>", 2);
+        tree.add_path("/posts(/:page)?", 3);
+        tree.add_path("/k", 4);
+
+        for pattern in &[
+            "/users",
+            "/USERS",
+            "/Users/:name<int>",
+            "/Posts",
+            "/POSTS/:p",
+            "/\u{212A}",
+            "/K",
+        ] {
+            assert_eq!(
+                tree.try_add_path(pattern, 5),
+                Err(RouteError::DuplicateRoute {
+                    pattern: pattern.to_string(),
+                })
+            );
+        }
+        tree.add_path("/Users/:id<uuid>", 6);
+
+        assert_eq!(tree.find_test("/users"), Some(&1));
+        assert_eq!(tree.find_test("/USERS/42"), Some(&2));
+        assert_eq!(tree.find_test("/\u{212A}"), Some(&4));
+        assert_eq!(tree.find_test("/K"), Some(&4));
+
+        assert_eq!(tree.remove("/Users"), Some(1));
+        tree.add_path("/users", 7);
+        assert_eq!(tree.find_test("/USERS"), Some(&7));
+    }
+
+    #[test]
+    fn params_percent_decoded() {
+        let mut tree = Tree::new();
+        tree.add_path("/user/:name", 1);
+        tree.add_path("/files/*path", 2);
+
+        let (_, params) = tree.find("/user/John%20Doe").unwrap();
+        assert_eq!(params.get("name"), Some("John Doe"));
+        assert_eq!(params.get_raw("name"), Some("John%20Doe"));
+
+        let (_, params) = tree.find("/user/a%2Fb").unwrap();
+        assert_eq!(params.get("name"), Some("a/b"));
+
+        let (_, params) = tree.find("/files/a%20b/c").unwrap();
+        assert_eq!(params.get("path"), Some("a b/c"));
+        assert_eq!(params.get_raw("path"), Some("a%20b/c"));
+
+        // invalid UTF-8 or malformed escapes do not match
+        assert_eq!(tree.find_test("/user/%FF"), None);
+        assert_eq!(tree.find_test("/user/%zz"), None);
+        assert_eq!(tree.find_test("/files/%C3%28"), None);
+    }
+
+    #[test]
+    fn static_parts_match_raw_path() {
+        let mut tree = Tree::new();
+        tree.add_path("/a b/:id", 1);
+        tree.add_path("/a%20b/:id", 2);
+        assert_eq!(tree.find_test("/a%20b/1"), Some(&2));
+        assert_eq!(tree.find_test("/a b/1"), Some(&1));
+    }
+
+    #[test]
+    fn unicode() {
+        let mut tree = Tree::new();
+        tree.add_path("/café/:id", 1);
+        tree.add_path("/cafè", 2);
+        tree.add_path("/日本/語", 3);
+        tree.add_path("/日本/:word/more", 4);
+        tree.add_path("/ü", 5);
+        tree.add_path("/u", 6);
+
+        assert_eq!(tree.find_test("/café/42"), Some(&1));
+        assert_eq!(tree.find_test("/cafè"), Some(&2));
+        assert_eq!(tree.find_test("/caf"), None);
+        assert_eq!(tree.find_test("/日本/語"), Some(&3));
+        assert_eq!(tree.find_test("/日本/言葉/more"), Some(&4));
+        assert_eq!(tree.find_test("/日"), None);
+        assert_eq!(tree.find_test("/ü"), Some(&5));
+        assert_eq!(tree.find_test("/u"), Some(&6));
+
+        let (_, params) = tree.find("/日本/言葉/more").unwrap();
+        assert_eq!(params.get("word"), Some("言葉"));
+    }
+
+    #[test]
+    fn unicode_percent_encoded() {
+        let mut tree = Tree::new();
+        tree.add_path("/café/:id", 1);
+        tree.add_path("/cafè", 2);
+        tree.add_path("/日本/:word/more", 3);
+        tree.add_path("/price/:amount€", 4);
+        tree.add_path("/a%2Fb", 5);
+
+        for &frozen in &[false, true] {
+            if frozen {
+                tree.freeze();
+            }
+            assert_eq!(tree.find_test("/caf%C3%A9/42"), Some(&1));
+            assert_eq!(tree.find_test("/caf%c3%a9/42"), Some(&1));
+            assert_eq!(tree.find_test("/caf%C3%A8"), Some(&2));
+            assert_eq!(tree.find_test("/caf%C3"), None);
+            assert_eq!(tree.find_test("/caf%C3%A9%A9/42"), None);
+            assert_eq!(tree.find_test("/a%2Fb"), Some(&5));
+            assert_eq!(tree.find_test("/a/b"), None);
+
+            let (_, params) = tree.find("/%E6%97%A5%E6%9C%AC/%E8%A8%80/more").unwrap();
+            assert_eq!(params.get("word"), Some("言"));
+            assert_eq!(params.get_raw("word"), Some("%E8%A8%80"));
+            let (_, params) = tree.find("/price/42%E2%82%AC").unwrap();
+            assert_eq!(params.get("amount"), Some("42"));
+        }
+    }
+
+    fn without(s: &str, chars: &str) -> String {
+        s.chars().filter(|ch| !chars.contains(*ch)).collect()
+    }
+
+    #[test]
+    fn unicode_static_routes() {
+        fn prop(routes: Vec<String>) -> bool {
+            let routes: BTreeSet<String> = routes
+                .iter()
+                .map(|route| format!("/{}", without(route, ":*()?")))
+                .collect();
+            let mut tree = Tree::new();
+            for (i, route) in routes.iter().enumerate() {
+                tree.add_path(route, i);
+            }
+            routes
+                .iter()
+                .enumerate()
+                .all(|(i, route)| tree.find_test(route) == Some(&i))
+        }
+        quickcheck(prop as fn(Vec<String>) -> bool);
+    }
+
+    #[test]
+    fn unicode_params() {
+        fn prop(prefix: String, suffix: String, value: String) -> TestResult {
+            let (prefix, suffix) = (without(&prefix, ":*()?"), without(&suffix, ":*()?"));
+            let value = without(&value, "/.%");
+            if value.is_empty() {
+                return TestResult::discard();
+            }
+
+            let pattern = format!("/{}/:id/{}", prefix, suffix);
+            let other = format!("/{}/{}", prefix, suffix);
+            let mut tree = Tree::new();
+            tree.add_path(&pattern, 1);
+            tree.add_path(&other, 2);
+
+            let path = format!("/{}/{}/{}", prefix, value, suffix);
+            let found = tree.find(&path);
+            TestResult::from_bool(
+                found.as_ref().map(|(v, _)| *v) == Some(&1)
+                    && found.as_ref().and_then(|(_, p)| p.get("id")) == Some(value.as_str())
+                    && tree.find_test(&other) == Some(&2),
+            )
+        }
+        quickcheck(prop as fn(String, String, String) -> TestResult);
+    }
+
+    #[test]
+    fn toggled_trailing_slash() {
+        let mut tree = Tree::new();
+        tree.add_path("/", 1);
+        tree.add_path("/foo", 2);
+        tree.add_path("/bar/", 3);
+        tree.add_path("/users/:id/", 4);
+        tree.add_path("/static/*path", 5);
+        tree.add_path("/baz", 6);
+        tree.add_path("/baz/qux/", 7);
+
+        let find = |path| tree.find_toggled_trailing_slash(path).map(|v| v.0);
+        assert_eq!(find("/"), None);
+        assert_eq!(find("/foo/"), Some(&2));
+        assert_eq!(find("/foo"), None);
+        assert_eq!(find("/bar"), Some(&3));
+        assert_eq!(find("/bar/"), None);
+        assert_eq!(find("/users/42"), Some(&4));
+        assert_eq!(find("/static"), Some(&5));
+        assert_eq!(find("/baz/qux"), Some(&7));
+        assert_eq!(find("/baz/"), Some(&6));
+        assert_eq!(find("/ba"), None);
+
+        let (_, params) = tree.find_toggled_trailing_slash("/users/42").unwrap();
+        assert_eq!(params.get("id"), Some("42"));
+        let (_, params) = tree.find_toggled_trailing_slash("/static").unwrap();
+        assert_eq!(params.get("path"), Some(""));
+    }
+
+    #[test]
+    fn build() {
+        let pattern = "/articles/:article/comments/:comment";
+        assert_eq!(
+            build_path(pattern, &[("comment", "13"), ("article", "42")]),
+            Ok("/articles/42/comments/13".to_string())
+        );
+        assert_eq!(
+            build_path("/user/:name.json", &[("name", "John Doe")]),
+            Ok("/user/John%20Doe.json".to_string())
+        );
+        assert_eq!(
+            build_path("/static/*path", &[("path", "js/app.js")]),
+            Ok("/static/js/app.js".to_string())
+        );
+        assert_eq!(build_path("/foo", &[]), Ok("/foo".to_string()));
+        assert_eq!(
+            build_path("/posts(/:page)?", &[("page", "2")]),
+            Ok("/posts/2".to_string())
+        );
+        assert_eq!(build_path("/posts(/:page)?", &[]), Ok("/posts".to_string()));
+        assert_eq!(
+            build_path("/a(/:b)?(/:c)?", &[("c", "3")]),
+            Ok("/a/3".to_string())
+        );
+        assert_eq!(
+            build_path("/range/:from-:to", &[("from", "a-b"), ("to", "c")]),
+            Ok("/range/a%2Db-c".to_string())
+        );
+        assert_eq!(
+            build_path(pattern, &[("article", "42")]),
+            Err(UrlError::MissingParam {
+                name: "comment".to_string()
+            })
+        );
+        assert_eq!(
+            build_path(
+                pattern,
+                &[("article", "42"), ("comment", "13"), ("foo", "bar")]
+            ),
+            Err(UrlError::ExtraParam {
+                name: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            build_path(pattern, &[("article", ""), ("comment", "1")]),
+            Err(UrlError::InvalidParam {
+                name: "article".to_string(),
+                value: "".to_string(),
+            })
+        );
+        assert_eq!(
+            build_path("/static/*path", &[("path", "")]),
+            Ok("/static/".to_string())
+        );
+        let mut tree = Tree::new();
+        tree.add_path("/static/*path", 1);
+        let (_, params) = tree.find("/static/").unwrap();
+        assert_eq!(params.get("path"), Some(""));
+        assert_eq!(
+            build_path("/posts(/:page)?", &[("page", "")]),
+            Err(UrlError::InvalidParam {
+                name: "page".to_string(),
+                value: "".to_string(),
+            })
+        );
+        assert_eq!(
+            build_path("/users/:id<int>", &[("id", "abc")]),
+            Err(UrlError::InvalidParam {
+                name: "id".to_string(),
+                value: "abc".to_string(),
+            })
+        );
+        assert_eq!(
+            build_path("/users/:id<int>", &[("id", "42")]),
+            Ok("/users/42".to_string())
+        );
+        assert_eq!(
+            build_path(
+                pattern,
+                &[("article", "1"), ("comment", "2"), ("article", "3")]
+            ),
+            Err(UrlError::DuplicateParam {
+                name: "article".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn build_roundtrip() {
+        fn prop(a: String, b: String) -> TestResult {
+            if a.is_empty() || b.is_empty() {
+                return TestResult::discard();
+            }
+            let pattern = "/a/:a.json/b/*b";
+            let mut tree = Tree::new();
+            tree.add_path(pattern, 1);
+
+            let path = build_path(pattern, &[("a", &a), ("b", &b)]).unwrap();
+            let (_, params) = tree.find(&path).unwrap();
+            TestResult::from_bool(
+                params.get("a") == Some(&a[..]) && params.get("b") == Some(&b[..]),
+            )
+        }
+        quickcheck(prop as fn(String, String) -> TestResult);
+    }
+
+    #[test]
+    fn into_routes() {
+        let patterns = [
+            "/",
+            "/articles/:article",
+            "/articles/:article.json",
+            "/articles/:article/comments/:comment",
+            "/articles/:id<int>",
+            "/foo",
+            "/foobar",
+            "/posts(/:page)?",
+            "/static/*path",
+        ];
+        let mut tree = Tree::new();
+        for (i, pattern) in patterns.iter().enumerate().rev() {
+            tree.add_path(pattern, i);
+        }
+        assert_eq!(
+            tree.into_routes(),
+            patterns
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, pattern)| (pattern.to_string(), i))
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -0,0 +1,430 @@
+use std::ops::Range;
+
+use super::{char_indices, eq_ignore_case, next_char, strip_prefix, Node as TreeNode};
+use constraint::Constraint;
+use params::Params;
+
+/// An immutable, flattened copy of a tree's nodes (see `Tree::freeze`). All nodes live in a
+/// single `Vec` and refer to each other by index, their paths and names are slices of a single
+/// string, and the static childs of a node are a range of the first bytes of their paths (sorted,
+/// so they can be binary searched). Resolving a path thus neither hashes nor chases pointers.
+/// The values are the route indices of the tree.
+#[derive(Debug)]
+pub struct Matcher {
+    nodes: Vec<Node>,
+    /// The first byte of the path of every static child, grouped by parent and sorted.
+    keys: Vec<u8>,
+    /// The node index of every static child (parallel to `keys`).
+    childs: Vec<u32>,
+    params: Vec<Param>,
+    /// The concatenated paths of all nodes and names of all params and catch-alls.
+    strings: String,
+}
+
+#[derive(Debug)]
+struct Node {
+    path: Span,
+    value: Option<u32>,
+    /// The range of this node's static childs in `keys` and `childs`.
+    childs: Span,
+    /// The range of this node's params in `params`, in the order they are tried.
+    params: Span,
+    catch_all: Option<CatchAll>,
+}
+
+#[derive(Debug)]
+struct Param {
+    name: Span,
+    constraint: Option<Constraint>,
+    node: u32,
+}
+
+#[derive(Debug)]
+struct CatchAll {
+    name: Span,
+    value: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Span {
+    start: u32,
+    end: u32,
+}
+
+impl Span {
+    fn range(self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
+impl Matcher {
+    pub fn new(root: &TreeNode<usize>) -> Self {
+        let mut matcher = Matcher {
+            nodes: Vec::new(),
+            keys: Vec::new(),
+            childs: Vec::new(),
+            params: Vec::new(),
+            strings: String::new(),
+        };
+        matcher.add(root);
+        matcher
+    }
+
+    /// Adds `node` and (recursively) all its childs and returns its index.
+    fn add(&mut self, node: &TreeNode<usize>) -> u32 {
+        let index = self.nodes.len();
+        let path = self.intern(&node.path);
+        let catch_all = node
+            .catch_all
+            .as_ref()
+            .and_then(|catch_all| catch_all.value.map(|value| (&catch_all.name, value as u32)));
+        let catch_all = catch_all.map(|(name, value)| CatchAll {
+            name: self.intern(name),
+            value,
+        });
+        self.nodes.push(Node {
+            path,
+            value: node.value.map(|value| value as u32),
+            childs: Span::default(),
+            params: Span::default(),
+            catch_all,
+        });
+
+        // reserve the ranges first, so that the childs and params of a node are contiguous
+        let mut childs = node.childs.iter().collect::<Vec<_>>();
+        childs.sort_by_key(|&(&ch, _)| ch);
+        let childs_start = self.keys.len();
+        for &(_, child) in &childs {
+            self.keys.push(child.path.as_bytes()[0]);
+            self.childs.push(0);
+        }
+        let params_start = self.params.len();
+        for param in &node.wildcards {
+            let name = self.intern(&param.name);
+            self.params.push(Param {
+                name,
+                constraint: param.constraint.clone(),
+                node: 0,
+            });
+        }
+
+        for (i, &(_, child)) in childs.iter().enumerate() {
+            self.childs[childs_start + i] = self.add(child);
+        }
+        for (i, param) in node.wildcards.iter().enumerate() {
+            self.params[params_start + i].node = self.add(&param.node);
+        }
+
+        self.nodes[index].childs = span(childs_start, childs.len());
+        self.nodes[index].params = span(params_start, node.wildcards.len());
+        index as u32
+    }
+
+    fn intern(&mut self, s: &str) -> Span {
+        let start = self.strings.len();
+        self.strings.push_str(s);
+        span(start, s.len())
+    }
+
+    fn str(&self, span: Span) -> &str {
+        &self.strings[span.range()]
+    }
+
+    /// Finds the route index for `path`, see `Node::find` of the tree it has been created from.
+    pub fn find<'m, 'p>(
+        &'m self,
+        path: &'p str,
+        params: &mut Params<'m, 'p>,
+        add_slash: bool,
+        ignore_case: bool,
+    ) -> Option<usize> {
+        self.find_node(0, path, params, add_slash, ignore_case)
+            .map(|index| index as usize)
+    }
+
+    fn find_node<'m, 'p>(
+        &'m self,
+        index: u32,
+        path: &'p str,
+        params: &mut Params<'m, 'p>,
+        add_slash: bool,
+        ignore_case: bool,
+    ) -> Option<u32> {
+        let node = &self.nodes[index as usize];
+        let path = match strip_prefix(path, self.str(node.path), ignore_case) {
+            Some(path) => path,
+            None => {
+                if add_slash && strip_prefix(self.str(node.path), path, ignore_case) == Some("/") {
+                    // the path fully matches with the added slash
+                    let rest = &path[path.len()..];
+                    return node
+                        .value
+                        .or_else(|| self.find_catch_all(node, rest, params));
+                }
+                return None;
+            }
+        };
+
+        // static childs are tried first, then the params and finally the catch-all
+        let found = match path.chars().next() {
+            None if add_slash => self.find_child(node, "/", false, |child| {
+                self.find_node(child, path, params, add_slash, ignore_case)
+            }),
+            None => node.value,
+            Some(_) => self
+                .find_child(node, path, ignore_case, |child| {
+                    self.find_node(child, path, params, add_slash, ignore_case)
+                })
+                .or_else(|| {
+                    self.params[node.params.range()].iter().find_map(|param| {
+                        self.find_param(param, path, params, add_slash, ignore_case)
+                    })
+                }),
+        };
+
+        found.or_else(|| self.find_catch_all(node, path, params))
+    }
+
+    fn find_param<'m, 'p>(
+        &'m self,
+        param: &'m Param,
+        path: &'p str,
+        params: &mut Params<'m, 'p>,
+        add_slash: bool,
+        ignore_case: bool,
+    ) -> Option<u32> {
+        let end = path.find('/').unwrap_or(path.len());
+        let ends = char_indices(&path[..end])
+            .skip(1)
+            .filter(|&(i, _)| self.continues_with(param.node, &path[i..], ignore_case))
+            .map(|(i, _)| i)
+            .chain(Some(end));
+
+        let len = params.len();
+        for i in ends {
+            let (value, rest) = path.split_at(i);
+            if value.is_empty() || !params.push(self.str(param.name), value) {
+                continue;
+            }
+            let is_allowed = match param.constraint {
+                Some(ref constraint) => {
+                    params.last().is_some_and(|value| constraint.matches(value))
+                }
+                None => true,
+            };
+            if is_allowed {
+                if let Some(found) =
+                    self.find_node(param.node, rest, params, add_slash, ignore_case)
+                {
+                    return Some(found);
+                }
+            }
+            params.truncate(len);
+        }
+        None
+    }
+
+    fn find_catch_all<'m, 'p>(
+        &'m self,
+        node: &'m Node,
+        path: &'p str,
+        params: &mut Params<'m, 'p>,
+    ) -> Option<u32> {
+        let catch_all = node.catch_all.as_ref()?;
+        if !params.push(self.str(catch_all.name), path) {
+            return None;
+        }
+        Some(catch_all.value)
+    }
+
+    /// Calls `f` with the static childs `path` (which must not be empty) might continue in, the
+    /// exact match first, until it returns a result.
+    fn find_child<F>(&self, node: &Node, path: &str, ignore_case: bool, mut f: F) -> Option<u32>
+    where
+        F: FnMut(u32) -> Option<u32>,
+    {
+        let (ch, _) = next_char(path).expect("path is not empty");
+        let byte = ch.encode_utf8(&mut [0; 4]).as_bytes()[0];
+        let range = node.childs.range();
+        let keys = &self.keys[range.clone()];
+        // different non-ASCII characters might start with the same byte, so all childs with the
+        // matching key are checked
+        let first = keys.partition_point(|&key| key < byte);
+        for (i, _) in keys[first..]
+            .iter()
+            .enumerate()
+            .take_while(|&(_, &key)| key == byte)
+        {
+            let child = self.childs[range.start + first + i];
+            if byte.is_ascii() || self.first_char(child) == ch {
+                if let Some(found) = f(child) {
+                    return Some(found);
+                }
+            }
+        }
+
+        if ignore_case {
+            for &child in &self.childs[range] {
+                let key = self.first_char(child);
+                if key != ch && eq_ignore_case(key, ch) {
+                    if let Some(found) = f(child) {
+                        return Some(found);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn first_char(&self, child: u32) -> char {
+        let path = self.str(self.nodes[child as usize].path);
+        path.chars().next().expect("static childs have a path")
+    }
+
+    /// Whether `path` (which must not be empty) could match the node at `index` (ignoring its
+    /// params).
+    fn continues_with(&self, index: u32, path: &str, ignore_case: bool) -> bool {
+        let node = &self.nodes[index as usize];
+        let (ch, _) = next_char(path).expect("path is not empty");
+        match self.str(node.path).chars().next() {
+            Some(first_char) if ignore_case => eq_ignore_case(first_char, ch),
+            Some(first_char) => first_char == ch,
+            None => self.find_child(node, path, ignore_case, Some).is_some(),
+        }
+    }
+}
+
+fn span(start: usize, len: usize) -> Span {
+    Span {
+        start: start as u32,
+        end: (start + len) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use params::{OwnedParams, Params};
+    use quickcheck::quickcheck;
+    use tree::Tree;
+
+    type Found = Option<(usize, OwnedParams)>;
+
+    fn find_all(tree: &Tree<usize>, paths: &[&str]) -> Vec<(Found, Found)> {
+        let owned = |found: Option<(&usize, Params)>| found.map(|(&v, p)| (v, p.into_owned()));
+        paths
+            .iter()
+            .map(|path| {
+                (
+                    owned(tree.find(path)),
+                    owned(tree.find_toggled_trailing_slash(path)),
+                )
+            })
+            .collect()
+    }
+
+    /// Asserts that the frozen tree resolves all `paths` exactly like the tree itself.
+    fn assert_same(mut tree: Tree<usize>, paths: &[&str]) {
+        let expected = find_all(&tree, paths);
+        tree.freeze();
+        assert!(tree.frozen.is_some());
+        assert_eq!(find_all(&tree, paths), expected);
+    }
+
+    #[test]
+    fn same_as_tree() {
+        let routes = [
+            "/",
+            "/users",
+            "/users/new",
+            "/users/:id<int>",
+            "/users/:name",
+            "/users/:name/posts/",
+            "/files/*path",
+            "/pkg/:name@:version",
+            "/range/:from-:to",
+            "/posts(/:page)?",
+            "/café/menu",
+            "/cafè/menu",
+            "/CAFÉ",
+            "/static/",
+            "/a/:b/c",
+            "/a/*rest",
+        ];
+        let paths = [
+            "",
+            "/",
+            "/users",
+            "/users/",
+            "/users/new",
+            "/users/42",
+            "/users/rkusa",
+            "/users/rkusa/posts",
+            "/users/rkusa/posts/",
+            "/USERS/Rkusa/Posts",
+            "/files",
+            "/files/",
+            "/files/a/b%20c",
+            "/pkg/router@0.1.0",
+            "/pkg/@0.1.0",
+            "/range/2018-10",
+            "/posts",
+            "/posts/2",
+            "/café/menu",
+            "/cafè/menu",
+            "/CAFÉ/MENU",
+            "/café",
+            "/static",
+            "/a/b/c",
+            "/a/b/d",
+            "/a/%ff/c",
+            "/unknown",
+        ];
+
+        for &ignore_case in &[false, true] {
+            let mut tree = Tree::new();
+            tree.set_ignore_case(ignore_case);
+            for (i, route) in routes.iter().enumerate() {
+                tree.add_path(route, i);
+            }
+            assert_same(tree, &paths);
+        }
+    }
+
+    #[test]
+    fn same_as_tree_for_any_routes() {
+        fn prop(routes: Vec<String>, paths: Vec<String>, ignore_case: bool) -> bool {
+            let mut tree = Tree::new();
+            tree.set_ignore_case(ignore_case);
+            for (i, route) in routes.iter().enumerate() {
+                // invalid or conflicting routes are just skipped
+                let _ = tree.try_add_path(&format!("/{}", route), i);
+            }
+
+            let paths = routes
+                .iter()
+                .chain(paths.iter())
+                .map(|path| format!("/{}", path))
+                .collect::<Vec<_>>();
+            let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+            let expected = find_all(&tree, &paths);
+            tree.freeze();
+            find_all(&tree, &paths) == expected
+        }
+        quickcheck(prop as fn(Vec<String>, Vec<String>, bool) -> bool);
+    }
+
+    #[test]
+    fn modifications_unfreeze() {
+        let mut tree = Tree::new();
+        tree.add_path("/foo", 0);
+        tree.freeze();
+        tree.add_path("/bar", 1);
+        assert!(tree.frozen.is_none());
+        assert_eq!(tree.find_test("/bar"), Some(&1));
+
+        tree.freeze();
+        assert_eq!(tree.remove("/foo"), Some(0));
+        assert!(tree.frozen.is_none());
+        assert_eq!(tree.find_test("/foo"), None);
+    }
+}
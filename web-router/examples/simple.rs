@@ -7,24 +7,29 @@ extern crate web_router;
 use futures::Future;
 use hyper::StatusCode;
 use web::*;
-use web_router::{AsParams, Params, Router};
+use web_router::{AsParams, BadRequest, ParamError, Params, Router};
 
 struct State {
     params: Option<Params>,
 }
 
-struct Error;
+enum Error {
+    Params(ParamError),
+    Http,
+}
 
 fn main() {
     let mut app = App::new();
     let mut router: Router<State, Error> = Router::new();
     router.get("/foobar", foobar);
     router.get("/foocar", |_, mut res: Response, _| res.body("foocar"));
-    router.get("/user/:id", |_, mut res: Response, state: State| {
-        let params = state.params().unwrap();
-        let id = params.get("id").unwrap();
-        res.body(format!("id = {}", id))
-    });
+    router.get(
+        "/user/:id",
+        |_, mut res: Response, state: State| -> Result<_, Error> {
+            let id: u64 = state.params().unwrap().get_as("id")?;
+            Ok(res.body(format!("id = {}", id))?)
+        },
+    );
     app.add(router);
 
     let app = app.build();
@@ -58,14 +63,23 @@ impl AsParams for State {
     }
 }
 
+impl From<ParamError> for Error {
+    fn from(err: ParamError) -> Self {
+        Error::Params(err)
+    }
+}
+
 impl From<http::Error> for Error {
     fn from(_: http::Error) -> Self {
-        Error
+        Error::Http
     }
 }
 
 impl Into<HttpError> for Error {
     fn into(self) -> HttpError {
-        HttpError::Status(StatusCode::INTERNAL_SERVER_ERROR)
+        match self {
+            Error::Params(err) => BadRequest(err).into(),
+            Error::Http => HttpError::Status(StatusCode::INTERNAL_SERVER_ERROR),
+        }
     }
 }
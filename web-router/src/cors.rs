@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use http::header::{self, HeaderMap, HeaderValue};
+use http::Method;
+use web::Request;
+
+/// Configures how the router answers CORS preflight requests and which CORS headers it adds to
+/// the responses of matched routes (see `Router::set_cors`). By default, no origins and only the
+/// CORS-safelisted request headers are allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Cors {
+    any_origin: bool,
+    origins: Vec<String>,
+    any_header: bool,
+    headers: Vec<String>,
+    max_age: Option<Duration>,
+    credentials: bool,
+}
+
+impl Cors {
+    pub fn new() -> Self {
+        Cors::default()
+    }
+
+    /// Allows requests from the given origin, e.g. `https://example.com`.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        self.origins.push(origin.to_string());
+        self
+    }
+
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self
+    }
+
+    /// Allows requests to include the given header, e.g. `Authorization`.
+    pub fn allow_header(mut self, header: &str) -> Self {
+        self.headers.push(header.to_string());
+        self
+    }
+
+    /// Allows requests to include any header (by allowing the headers a preflight request asks
+    /// for).
+    pub fn allow_any_header(mut self) -> Self {
+        self.any_header = true;
+        self
+    }
+
+    /// How long browsers may cache the response to a preflight request.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Whether requests may include credentials, like cookies.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.credentials = allow;
+        self
+    }
+
+    /// Adds the CORS headers for the preflight request `req` to `headers`, unless its origin is
+    /// not allowed. `allow` are the methods allowed for the requested path.
+    pub(crate) fn preflight(&self, req: &Request, allow: &str, headers: &mut HeaderMap) {
+        if !self.add_origin(req, headers) {
+            return;
+        }
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_str(allow).expect("method names are valid header values"),
+        );
+
+        let requested_headers = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS);
+        match requested_headers {
+            Some(requested) if self.any_header => {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+            }
+            _ if !self.headers.is_empty() => {
+                if let Ok(value) = HeaderValue::from_str(&self.headers.join(", ")) {
+                    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+                }
+            }
+            _ => {}
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+    }
+
+    /// Returns the CORS headers for the response to the (non-preflight) request `req`. They are
+    /// empty if the request is not a cross-origin request or its origin is not allowed.
+    pub(crate) fn response_headers(&self, req: &Request) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        self.add_origin(req, &mut headers);
+        headers
+    }
+
+    /// Adds the `Access-Control-Allow-Origin` header (and the headers depending on it) for the
+    /// origin of `req` to `headers` and returns whether the origin is allowed.
+    fn add_origin(&self, req: &Request, headers: &mut HeaderMap) -> bool {
+        let origin = match req.headers().get(header::ORIGIN) {
+            Some(origin) => origin,
+            None => return false,
+        };
+        let is_allowed = self.any_origin
+            || self
+                .origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes());
+        if !is_allowed {
+            return false;
+        }
+
+        if self.any_origin && !self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+        } else {
+            // credentials are not allowed with a wildcard origin, so the origin is mirrored
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        if self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        true
+    }
+}
+
+/// Returns the method a CORS preflight request asks for, or `None` if `req` is not a preflight
+/// request.
+pub(crate) fn preflight_method(req: &Request) -> Option<Method> {
+    if req.method() != Method::OPTIONS || !req.headers().contains_key(header::ORIGIN) {
+        return None;
+    }
+    req.headers()
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+}
@@ -0,0 +1,126 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use http::Method;
+use web::{HttpError, Middleware, Request, Response, ResponseFuture};
+
+use {Endpoint, Handler};
+
+type GroupRoute<S> = (Method, String, Box<Handler<S, HttpError>>);
+
+/// A set of routes sharing a common path prefix and middlewares, created with `Router::group`.
+/// The middlewares of a group run in the order they have been added, before the handler of the
+/// matched route, and only apply to routes of that group (including nested groups).
+pub struct Group<S, E> {
+    routes: Vec<GroupRoute<S>>,
+    middlewares: Vec<Box<Middleware<S>>>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<S, E> Group<S, E>
+where
+    S: 'static,
+    E: Into<HttpError> + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Group {
+            routes: Vec::new(),
+            middlewares: Vec::new(),
+            _error: PhantomData,
+        }
+    }
+
+    pub fn middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware<S> + 'static,
+    {
+        self.middlewares.push(Box::new(middleware));
+    }
+
+    pub fn route<H>(&mut self, method: Method, path: &str, handler: H)
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.routes
+            .push((method, path.to_string(), Box::new(Endpoint::new(handler))));
+    }
+
+    /// Adds a nested group, whose routes are additionally wrapped with the middlewares of this
+    /// group.
+    pub fn group<F>(&mut self, prefix: &str, f: F)
+    where
+        F: FnOnce(&mut Group<S, E>),
+    {
+        let mut group = Group::new();
+        f(&mut group);
+        self.routes.extend(group.into_routes(prefix));
+    }
+
+    method!(options, Method::OPTIONS);
+    method!(get, Method::GET);
+    method!(post, Method::POST);
+    method!(put, Method::PUT);
+    method!(delete, Method::DELETE);
+    method!(head, Method::HEAD);
+    method!(patch, Method::PATCH);
+    method!(trace, Method::TRACE);
+    method!(connect, Method::CONNECT);
+
+    /// Returns all routes of the group with the `prefix` prepended to their paths and their
+    /// handlers wrapped with the group's middlewares. Like with `Router::mount`, a root route `/`
+    /// becomes the bare `prefix`.
+    pub(crate) fn into_routes(self, prefix: &str) -> Vec<GroupRoute<S>> {
+        let prefix = prefix.trim_end_matches('/');
+        let middlewares = Arc::new(self.middlewares);
+        self.routes
+            .into_iter()
+            .map(|(method, path, handler)| {
+                let handler: Box<Handler<S, HttpError>> = if middlewares.is_empty() {
+                    handler
+                } else {
+                    Box::new(Chain {
+                        middlewares: middlewares.clone(),
+                        handler: Arc::from(handler),
+                    })
+                };
+                let path = if path == "/" && !prefix.is_empty() {
+                    prefix.to_string()
+                } else {
+                    format!("{}{}", prefix, path)
+                };
+                (method, path, handler)
+            })
+            .collect()
+    }
+}
+
+struct Chain<S> {
+    middlewares: Arc<Vec<Box<Middleware<S>>>>,
+    handler: Arc<Handler<S, HttpError>>,
+}
+
+impl<S: 'static> Handler<S, HttpError> for Chain<S> {
+    fn handle(&self, req: Request, res: Response, state: S) -> ResponseFuture {
+        call(&self.middlewares, 0, &self.handler, req, res, state)
+    }
+}
+
+fn call<S: 'static>(
+    middlewares: &Arc<Vec<Box<Middleware<S>>>>,
+    i: usize,
+    handler: &Arc<Handler<S, HttpError>>,
+    req: Request,
+    res: Response,
+    state: S,
+) -> ResponseFuture {
+    match middlewares.get(i) {
+        Some(middleware) => {
+            let (middlewares, handler) = (middlewares.clone(), handler.clone());
+            let next = Box::new(move |req, res, state| {
+                call(&middlewares, i + 1, &handler, req, res, state)
+            });
+            middleware.handle(req, res, state, next)
+        }
+        None => handler.handle(req, res, state),
+    }
+}
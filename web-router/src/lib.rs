@@ -1,11 +1,16 @@
 extern crate futures;
 extern crate http;
+#[macro_use]
+extern crate log;
 extern crate router;
 extern crate web;
 
 use futures::Future;
-use http::Method;
-pub use router::Params;
+use http::{header, HeaderMap, Method, StatusCode};
+use router::ResolveError;
+pub use router::{OwnedParams as Params, ParamError, RouteError, TrailingSlash, UrlError};
+use std::fmt;
+use std::marker::PhantomData;
 use web::{HttpError, IntoResponse, Middleware, Next, Request, Response, ResponseFuture};
 
 pub trait Handler<S, E>: Send + Sync {
@@ -24,11 +29,45 @@ where
     }
 }
 
-pub struct Router<'a, S, E: Into<HttpError>>(router::Router<'a, Box<Handler<S, E>>>);
+/// Wraps a handler to convert its errors into `HttpError`s, so that handlers can be chained with
+/// middlewares (see `Group`).
+struct Endpoint<H, E> {
+    handler: H,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<H, E> Endpoint<H, E> {
+    fn new(handler: H) -> Self {
+        Endpoint {
+            handler,
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<S, E, H> Handler<S, HttpError> for Endpoint<H, E>
+where
+    E: Into<HttpError> + 'static,
+    H: Handler<S, E>,
+{
+    fn handle(&self, req: Request, res: Response, state: S) -> ResponseFuture {
+        Box::new(
+            self.handler
+                .handle(req, res, state)
+                .map_err(|err| err.into()),
+        )
+    }
+}
+
+pub struct Router<S, E: Into<HttpError>>(
+    router::Router<Box<Handler<S, HttpError>>>,
+    Option<Cors>,
+    PhantomData<fn() -> E>,
+);
 
 macro_rules! method {
     ( $name:ident, $method:expr ) => {
-        pub fn $name<H>(&mut self, path: &'a str, handler: H)
+        pub fn $name<H>(&mut self, path: &str, handler: H)
     where
         H: Handler<S, E> + 'static,
         {
@@ -37,19 +76,150 @@ macro_rules! method {
     };
 }
 
-impl<'a, S, E> Router<'a, S, E>
+mod cors;
+mod group;
+mod shared;
+pub use cors::Cors;
+pub use group::Group;
+pub use shared::SharedRouter;
+
+impl<S, E> Router<S, E>
 where
-    E: Into<HttpError>,
+    S: 'static,
+    E: Into<HttpError> + 'static,
 {
     pub fn new() -> Self {
         Router::default()
     }
 
-    pub fn route<H>(&mut self, method: Method, path: &'a str, handler: H)
+    pub fn route<H>(&mut self, method: Method, path: &str, handler: H)
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.0.route(method, path, Box::new(Endpoint::new(handler)));
+    }
+
+    pub fn try_route<H>(&mut self, method: Method, path: &str, handler: H) -> Result<(), RouteError>
     where
         H: Handler<S, E> + 'static,
     {
-        self.0.route(method, path, Box::new(handler));
+        self.0
+            .try_route(method, path, Box::new(Endpoint::new(handler)))
+    }
+
+    /// Registers a route for all methods, routes for a specific method take precedence (see
+    /// `router::Router::any`).
+    pub fn any<H>(&mut self, path: &str, handler: H)
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.0.any(path, Box::new(Endpoint::new(handler)));
+    }
+
+    pub fn try_any<H>(&mut self, path: &str, handler: H) -> Result<(), RouteError>
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.0.try_any(path, Box::new(Endpoint::new(handler)))
+    }
+
+    pub fn route_named<H>(&mut self, name: &str, method: Method, path: &str, handler: H)
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.0
+            .route_named(name, method, path, Box::new(Endpoint::new(handler)));
+    }
+
+    pub fn try_route_named<H>(
+        &mut self,
+        name: &str,
+        method: Method,
+        path: &str,
+        handler: H,
+    ) -> Result<(), RouteError>
+    where
+        H: Handler<S, E> + 'static,
+    {
+        self.0
+            .try_route_named(name, method, path, Box::new(Endpoint::new(handler)))
+    }
+
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.0.url_for(name, params)
+    }
+
+    /// Returns the method and pattern of all routes registered for a specific method, sorted by
+    /// pattern and method. Routes registered with `any` are not included, see `any_routes`.
+    pub fn routes(&self) -> impl Iterator<Item = (&Method, &str)> {
+        self.0
+            .routes()
+            .map(|(method, pattern, _)| (method, pattern))
+    }
+
+    /// Returns the pattern of all routes registered with `any`, sorted by pattern.
+    pub fn any_routes(&self) -> impl Iterator<Item = &str> {
+        self.0.any_routes().map(|(pattern, _)| pattern)
+    }
+
+    /// Merges all routes of `other` into this router, with `prefix` (which can contain params)
+    /// prepended to their patterns. A root route `/` of `other` becomes the bare `prefix`.
+    pub fn mount(&mut self, prefix: &str, other: Router<S, E>) {
+        self.0.mount(prefix, other.0);
+    }
+
+    /// Like `mount`, but returns an error instead of panicking if a route of `other` conflicts
+    /// with an existing one. The router is left unchanged in that case.
+    pub fn try_mount(&mut self, prefix: &str, other: Router<S, E>) -> Result<(), RouteError> {
+        self.0.try_mount(prefix, other.0)
+    }
+
+    /// Adds a group of routes that share the given path `prefix` and the middlewares added to the
+    /// group inside of `f`.
+    pub fn group<F>(&mut self, prefix: &str, f: F)
+    where
+        F: FnOnce(&mut Group<S, E>),
+    {
+        if let Err(err) = self.try_group(prefix, f) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `group`, but returns an error instead of panicking if a route of the group conflicts
+    /// with an existing one. The router is left unchanged in that case.
+    pub fn try_group<F>(&mut self, prefix: &str, f: F) -> Result<(), RouteError>
+    where
+        F: FnOnce(&mut Group<S, E>),
+    {
+        let mut group = Group::new();
+        f(&mut group);
+        let mut routes = router::Router::default();
+        for (method, path, handler) in group.into_routes(prefix) {
+            routes.try_route(method, &path, handler)?;
+        }
+        self.0.try_mount("", routes)
+    }
+
+    /// Flattens the routes into a structure that is faster to resolve, see `router::Router::freeze`.
+    pub fn freeze(&mut self) {
+        self.0.freeze();
+    }
+
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.0.set_trailing_slash(policy);
+    }
+
+    /// Answers CORS preflight requests to all routes according to `cors` (even if there is an
+    /// OPTIONS route for the requested path) and adds the CORS headers to the responses of
+    /// matched routes, including `405 Method Not Allowed`, redirects and automatic OPTIONS
+    /// responses. Responses of handlers that fail with an error do not get them.
+    pub fn set_cors(&mut self, cors: Cors) {
+        self.1 = Some(cors);
+    }
+
+    /// Matches the static parts of all routes case-insensitively, params keep their casing.
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.0.set_case_insensitive(enabled);
     }
 
     method!(options, Method::OPTIONS);
@@ -59,14 +229,40 @@ where
     method!(delete, Method::DELETE);
     method!(head, Method::HEAD);
     method!(patch, Method::PATCH);
+    method!(trace, Method::TRACE);
+    method!(connect, Method::CONNECT);
 }
 
-impl<'a, S, E> Default for Router<'a, S, E>
+impl<S, E> Default for Router<S, E>
 where
     E: Into<HttpError>,
 {
     fn default() -> Self {
-        Router(router::Router::default())
+        Router(router::Router::default(), None, PhantomData)
+    }
+}
+
+/// Wraps a `ParamError` to respond with `400 Bad Request`, e.g. when converting a handler's own
+/// error type into `HttpError`. As `HttpError` only carries the status, the reason is logged.
+#[derive(Debug)]
+pub struct BadRequest(pub ParamError);
+
+impl fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad request: {}", self.0)
+    }
+}
+
+impl From<ParamError> for BadRequest {
+    fn from(err: ParamError) -> Self {
+        BadRequest(err)
+    }
+}
+
+impl From<BadRequest> for HttpError {
+    fn from(err: BadRequest) -> Self {
+        debug!("{}", err);
+        HttpError::Status(StatusCode::BAD_REQUEST)
     }
 }
 
@@ -75,22 +271,125 @@ pub trait AsParams {
     fn params(&self) -> Option<&Params>;
 }
 
-impl<'a, S, E> Middleware<S> for Router<'a, S, E>
+impl<S, E> Middleware<S> for Router<S, E>
 where
     S: AsParams,
     E: Into<HttpError> + 'static,
 {
     fn handle(&self, req: Request, res: Response, state: S, next: Next<S>) -> ResponseFuture {
-        if let Some((mw, params)) = self.0.resolve(req.method(), req.uri().path()) {
-            let state = state.with_params(params);
-            let fut = mw.handle(req, res, state).map_err(|err| err.into());
-            Box::new(fut)
+        if let Some(ref cors) = self.1 {
+            if let Some(method) = cors::preflight_method(&req) {
+                let path = req.uri().path();
+                if self.0.resolve(&method, path).is_some() {
+                    let mut allowed = self.0.allowed_methods(path);
+                    if !allowed.contains(&method) {
+                        // an extension method handled by an `any` route
+                        allowed.push(method);
+                    }
+                    let allow = allow_header(allowed);
+                    let mut res = res;
+                    res.status(StatusCode::NO_CONTENT)
+                        .header(header::ALLOW, allow.as_str());
+                    if let Some(headers) = res.headers_mut() {
+                        cors.preflight(&req, &allow, headers);
+                    }
+                    return Box::new(Ok::<_, HttpError>(res).into_response());
+                }
+            }
+        }
+
+        let cors_headers = self
+            .1
+            .as_ref()
+            .map(|cors| cors.response_headers(&req))
+            .filter(|headers| !headers.is_empty());
+        match self.0.try_resolve(req.method(), req.uri().path()) {
+            Ok((mw, params)) => {
+                let is_head = req.method() == Method::HEAD;
+                let state = state.with_params(params.into_owned());
+                let mut fut = mw.handle(req, res, state);
+                if let Some(cors_headers) = cors_headers {
+                    fut = Box::new(fut.map(move |mut res| {
+                        add_cors_headers(res.headers_mut(), &cors_headers);
+                        res
+                    }));
+                }
+                if is_head {
+                    // HEAD requests might have been resolved to a GET handler, so strip the body
+                    // but keep all headers (e.g. Content-Length) as they are
+                    Box::new(fut.map(|res| {
+                        let (parts, _) = res.into_parts();
+                        http::Response::from_parts(parts, Default::default())
+                    }))
+                } else {
+                    fut
+                }
+            }
+            Err(ResolveError::MethodNotAllowed { allowed }) => {
+                // OPTIONS requests are answered with the allowed methods, unless there is an
+                // OPTIONS route for the path
+                let status = if req.method() == Method::OPTIONS {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::METHOD_NOT_ALLOWED
+                };
+                let mut res = res;
+                res.status(status)
+                    .header(header::ALLOW, allow_header(allowed).as_str());
+                if let (Some(cors_headers), Some(headers)) = (cors_headers, res.headers_mut()) {
+                    add_cors_headers(headers, &cors_headers);
+                }
+                Box::new(Ok::<_, HttpError>(res).into_response())
+            }
+            Err(ResolveError::Redirect { location }) => {
+                let location = match req.uri().query() {
+                    Some(query) => format!("{}?{}", location, query),
+                    None => location,
+                };
+                // 301 might change the method of the redirected request to GET, 308 never does
+                let status = if req.method() == Method::GET || req.method() == Method::HEAD {
+                    StatusCode::MOVED_PERMANENTLY
+                } else {
+                    StatusCode::PERMANENT_REDIRECT
+                };
+                let mut res = res;
+                res.status(status)
+                    .header(header::LOCATION, location.as_str());
+                if let (Some(cors_headers), Some(headers)) = (cors_headers, res.headers_mut()) {
+                    add_cors_headers(headers, &cors_headers);
+                }
+                Box::new(Ok::<_, HttpError>(res).into_response())
+            }
+            Err(ResolveError::NotFound) => next(req, res, state),
+        }
+    }
+}
+
+/// Adds the CORS headers of a matched path to the `headers` of its response, `Vary` is appended
+/// to the values the response already has.
+fn add_cors_headers(headers: &mut HeaderMap, cors_headers: &HeaderMap) {
+    for (name, value) in cors_headers {
+        if name == header::VARY {
+            headers.append(name, value.clone());
         } else {
-            next(req, res, state)
+            headers.insert(name, value.clone());
         }
     }
 }
 
+/// The value of the `Allow` header for the given methods, which always includes OPTIONS.
+fn allow_header(mut allowed: Vec<Method>) -> String {
+    if !allowed.contains(&Method::OPTIONS) {
+        allowed.push(Method::OPTIONS);
+    }
+    allowed.sort_by(|lhs, rhs| lhs.as_str().cmp(rhs.as_str()));
+    allowed
+        .iter()
+        .map(|method| method.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[cfg(test)]
 mod tests {
     extern crate futures;
@@ -98,9 +397,15 @@ mod tests {
 
     use self::futures::{Future, Stream};
     use self::hyper::Body;
-    use http::{self, StatusCode};
-    use web::{App, HttpError, IntoResponse, Response};
-    use {AsParams, Params, Router};
+    use http::{self, header, StatusCode};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use web::{App, HttpError, IntoResponse, Middleware, Next, Request, Response, ResponseFuture};
+    use {
+        AsParams, BadRequest, Cors, ParamError, Params, RouteError, Router, SharedRouter,
+        TrailingSlash,
+    };
 
     struct State {
         params: Option<Params>,
@@ -147,12 +452,64 @@ mod tests {
         assert_eq!(body, "Hello World!");
     }
 
+    #[test]
+    fn shared_router() {
+        fn router(body: &'static str) -> Router<State, HttpError> {
+            let mut router = Router::new();
+            router.get("/foo", move |_, mut res: Response, _| res.body(body));
+            router
+        }
+
+        let shared = SharedRouter::new(router("old"));
+        let mut app = App::new();
+        app.add(shared.clone());
+        let app = app.build();
+        let execute = || {
+            let req = http::Request::get("http://localhost/foo")
+                .body(Body::empty())
+                .unwrap();
+            let res = app
+                .execute(req, Response::new(), State::new(), |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                })
+                .wait()
+                .unwrap();
+            String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap()
+        };
+
+        assert_eq!(execute(), "old");
+        let snapshot = shared.snapshot();
+        let previous = shared.replace(router("new"));
+        assert!(Arc::ptr_eq(&snapshot, &previous));
+        assert_eq!(execute(), "new");
+
+        // a request that resolved against the old snapshot is not affected by the swap
+        let req = http::Request::get("http://localhost/foo")
+            .body(Body::empty())
+            .unwrap();
+        let next: Next<State> = Box::new(|_, _, _| unreachable!());
+        let res = snapshot
+            .handle(req, Response::new(), State::new(), next)
+            .wait()
+            .unwrap();
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "old");
+
+        let shared = shared.clone();
+        thread::spawn(move || shared.replace(Router::new()))
+            .join()
+            .unwrap();
+        assert_eq!(execute(), "");
+    }
+
     #[test]
     fn param_case_sensitivity() {
         let mut router: Router<State, HttpError> = Router::new();
         router.get("/test/:name", |_, mut res: Response, state: State| {
             let params = state.params().unwrap();
-            res.body(params.get("name").unwrap().clone())
+            res.body(params.get("name").unwrap().to_string())
         });
 
         let mut app = App::new();
@@ -173,4 +530,595 @@ mod tests {
         let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
         assert_eq!(body, "FooBar");
     }
+
+    #[test]
+    fn case_insensitive() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.set_case_insensitive(true);
+        router.get("/test/:name", |_, mut res: Response, state: State| {
+            let params = state.params().unwrap();
+            res.body(params.get("name").unwrap().to_string())
+        });
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+
+        for uri in &[
+            "http://localhost/test/FooBar",
+            "http://localhost/TEST/FooBar",
+        ] {
+            let req = http::Request::get(*uri).body(Body::empty()).unwrap();
+            let res = app
+                .execute(req, Response::default(), State::new(), |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                })
+                .wait()
+                .unwrap();
+            let body =
+                String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+            assert_eq!(body, "FooBar");
+        }
+    }
+
+    #[test]
+    fn method_not_allowed() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/foo", |_, mut res: Response, _| res.body("GET"));
+        router.post("/foo", |_, mut res: Response, _| res.body("POST"));
+
+        let mut app = App::new();
+        app.add(router);
+
+        let req = http::Request::put("http://localhost/foo")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .build()
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()[header::ALLOW], "GET, HEAD, OPTIONS, POST");
+    }
+
+    #[test]
+    fn auto_options() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/foo", |_, mut res: Response, _| res.body("GET"));
+        router.post("/foo", |_, mut res: Response, _| res.body("POST"));
+        router.options("/bar", |_, mut res: Response, _| res.body("OPTIONS"));
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+        let execute = |path: &str| {
+            let req = http::Request::options(format!("http://localhost{}", path))
+                .body(Body::empty())
+                .unwrap();
+            app.execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap()
+        };
+
+        let res = execute("/foo");
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[header::ALLOW], "GET, HEAD, OPTIONS, POST");
+
+        let res = execute("/bar");
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "OPTIONS");
+
+        let res = execute("/baz");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn cors_preflight() {
+        fn preflight(cors: &Cors, origin: &str, method: &str) -> http::Response<Body> {
+            let mut router: Router<State, HttpError> = Router::new();
+            router.get("/foo", |_, mut res: Response, _| res.body("GET"));
+            router.put("/foo", |_, mut res: Response, _| res.body("PUT"));
+            router.set_cors(cors.clone());
+            let mut app = App::new();
+            app.add(router);
+
+            let req = http::Request::options("http://localhost/foo")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-token")
+                .body(Body::empty())
+                .unwrap();
+            app.build()
+                .execute(req, Response::new(), State::new(), |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                })
+                .wait()
+                .unwrap()
+        }
+
+        let cors = Cors::new()
+            .allow_origin("https://example.com")
+            .allow_header("Authorization")
+            .max_age(Duration::from_secs(600));
+
+        let res = preflight(&cors, "https://example.com", "PUT");
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(headers[header::VARY], "Origin");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, HEAD, OPTIONS, PUT"
+        );
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "Authorization"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+        let res = preflight(&cors, "https://evil.com", "PUT");
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let res = preflight(&cors, "https://example.com", "DELETE");
+        assert_eq!(res.headers()[header::ALLOW], "GET, HEAD, OPTIONS, PUT");
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let cors = Cors::new().allow_any_origin().allow_any_header();
+        let res = preflight(&cors, "https://example.com", "GET");
+        assert_eq!(res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "x-token"
+        );
+        assert!(!res.headers().contains_key(header::VARY));
+
+        let cors = Cors::new().allow_any_origin().allow_credentials(true);
+        let res = preflight(&cors, "https://example.com", "GET");
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(headers[header::VARY], "Origin");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    }
+
+    #[test]
+    fn cors_response() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/foo", |_, mut res: Response, _| {
+            res.header(header::VARY, "Accept-Encoding").body("GET")
+        });
+        router.set_trailing_slash(TrailingSlash::Redirect);
+        router.set_cors(
+            Cors::new()
+                .allow_origin("https://example.com")
+                .allow_credentials(true),
+        );
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+        let execute = |origin: Option<&str>| {
+            let mut req = http::Request::get("http://localhost/foo");
+            if let Some(origin) = origin {
+                req.header(header::ORIGIN, origin);
+            }
+            app.execute(
+                req.body(Body::empty()).unwrap(),
+                Response::new(),
+                State::new(),
+                |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                },
+            )
+            .wait()
+            .unwrap()
+        };
+
+        let res = execute(Some("https://example.com"));
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(
+            headers.get_all(header::VARY).iter().collect::<Vec<_>>(),
+            vec!["Accept-Encoding", "Origin"]
+        );
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+
+        for &origin in &[Some("https://evil.com"), None] {
+            let res = execute(origin);
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(!res
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+            assert_eq!(res.headers()[header::VARY], "Accept-Encoding");
+        }
+
+        // responses the router sends itself get the CORS headers too
+        for &(method, uri, status) in &[
+            (
+                "POST",
+                "http://localhost/foo",
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            (
+                "GET",
+                "http://localhost/foo/",
+                StatusCode::MOVED_PERMANENTLY,
+            ),
+            ("OPTIONS", "http://localhost/foo", StatusCode::NO_CONTENT),
+        ] {
+            let req = http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::ORIGIN, "https://example.com")
+                .body(Body::empty())
+                .unwrap();
+            let res = app
+                .execute(req, Response::new(), State::new(), |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                })
+                .wait()
+                .unwrap();
+            assert_eq!(res.status(), status);
+            assert_eq!(
+                res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+                "https://example.com"
+            );
+            assert_eq!(res.headers()[header::VARY], "Origin");
+        }
+    }
+
+    #[test]
+    fn any_and_extension_methods() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.any("/dav/*path", |req: Request, mut res: Response, _| {
+            res.body(format!("any {}", req.method()))
+        });
+        router.route(
+            http::Method::from_bytes(b"PROPFIND").unwrap(),
+            "/dav/*path",
+            |_, mut res: Response, _| res.body("PROPFIND"),
+        );
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+
+        for &(method, expected) in &[("PROPFIND", "PROPFIND"), ("MKCOL", "any MKCOL")] {
+            let req = http::Request::builder()
+                .method(method)
+                .uri("http://localhost/dav/docs/")
+                .body(Body::empty())
+                .unwrap();
+            let res = app
+                .execute(req, Response::new(), State::new(), |_, _, _| {
+                    let mut res = Response::new();
+                    res.status(StatusCode::NOT_FOUND);
+                    Ok::<_, HttpError>(res).into_response()
+                })
+                .wait()
+                .unwrap();
+            let body =
+                String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+            assert_eq!(body, expected);
+        }
+    }
+
+    #[test]
+    fn head_falls_back_to_get() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/foo", |_, mut res: Response, _| {
+            res.header(header::CONTENT_LENGTH, "12")
+                .body("Hello World!")
+        });
+
+        let mut app = App::new();
+        app.add(router);
+
+        let req = http::Request::head("http://localhost/foo")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .build()
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "12");
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "");
+    }
+
+    #[test]
+    fn trailing_slash_redirect() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.set_trailing_slash(TrailingSlash::Redirect);
+        router.get("/foo", |_, mut res: Response, _| res.body("GET"));
+        router.post("/foo", |_, mut res: Response, _| res.body("POST"));
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+
+        let req = http::Request::get("http://localhost/foo/?bar=baz")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(res.headers()[header::LOCATION], "/foo?bar=baz");
+
+        let req = http::Request::post("http://localhost/foo/")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[header::LOCATION], "/foo");
+    }
+
+    #[test]
+    fn mount() {
+        let mut api: Router<State, HttpError> = Router::new();
+        api.get("/users/:id", |_, mut res: Response, state: State| {
+            let params = state.params().unwrap();
+            res.body(format!(
+                "{} {}",
+                params.get("version").unwrap(),
+                params.get("id").unwrap()
+            ))
+        });
+
+        let mut router: Router<State, HttpError> = Router::new();
+        router.mount("/api/:version", api);
+
+        let mut app = App::new();
+        app.add(router);
+
+        let req = http::Request::get("http://localhost/api/v1/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .build()
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "v1 42");
+    }
+
+    struct Auth;
+
+    impl Middleware<State> for Auth {
+        fn handle(
+            &self,
+            req: Request,
+            res: Response,
+            state: State,
+            next: Next<State>,
+        ) -> ResponseFuture {
+            if req.headers().contains_key(header::AUTHORIZATION) {
+                next(req, res, state)
+            } else {
+                let mut res = res;
+                res.status(StatusCode::UNAUTHORIZED);
+                Box::new(Ok::<_, HttpError>(res).into_response())
+            }
+        }
+    }
+
+    struct Trace(&'static str);
+
+    impl Middleware<State> for Trace {
+        fn handle(
+            &self,
+            req: Request,
+            res: Response,
+            state: State,
+            next: Next<State>,
+        ) -> ResponseFuture {
+            let mut res = res;
+            res.header("x-trace", self.0);
+            next(req, res, state)
+        }
+    }
+
+    #[test]
+    fn group() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/", |_, mut res: Response, _| res.body("public"));
+        router.group("/admin", |g| {
+            g.middleware(Trace("1"));
+            g.get("/users/:id", |_, mut res: Response, state: State| {
+                let params = state.params().unwrap();
+                res.body(params.get("id").unwrap().to_string())
+            });
+            g.group("/secret", |g| {
+                g.middleware(Auth);
+                g.get("/", |_, mut res: Response, _| res.body("secret"));
+            });
+            g.middleware(Trace("2"));
+        });
+        assert_eq!(
+            router.routes().collect::<Vec<_>>(),
+            vec![
+                (&http::Method::GET, "/"),
+                (&http::Method::GET, "/admin/secret"),
+                (&http::Method::GET, "/admin/users/:id"),
+            ]
+        );
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+        let execute = |req| {
+            app.execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap()
+        };
+
+        let res = execute(
+            http::Request::get("http://localhost/")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert!(res.headers().get("x-trace").is_none());
+
+        let res = execute(
+            http::Request::get("http://localhost/admin/users/42")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        let traces = res.headers().get_all("x-trace").iter().collect::<Vec<_>>();
+        assert_eq!(traces, vec!["1", "2"]);
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "42");
+
+        let res = execute(
+            http::Request::get("http://localhost/admin/secret")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = execute(
+            http::Request::get("http://localhost/admin/secret")
+                .header(header::AUTHORIZATION, "Bearer 42")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "secret");
+    }
+
+    #[test]
+    fn group_conflicts() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/admin/users", |_, mut res: Response, _| res.body("users"));
+
+        let result = router.try_group("/admin", |group| {
+            group.get("/settings", |_, mut res: Response, _| res.body("settings"));
+            group.get("/users", |_, mut res: Response, _| res.body("users"));
+        });
+        assert_eq!(
+            result,
+            Err(RouteError::DuplicateRoute {
+                pattern: "/admin/users".to_string()
+            })
+        );
+        assert_eq!(
+            router.routes().collect::<Vec<_>>(),
+            vec![(&http::Method::GET, "/admin/users")]
+        );
+    }
+
+    #[test]
+    fn any_routes() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/users", |_, mut res: Response, _| res.body("users"));
+        router.any("/proxy/*path", |_, mut res: Response, _| res.body("proxy"));
+        router.any("/health", |_, mut res: Response, _| res.body("ok"));
+
+        assert_eq!(
+            router.routes().collect::<Vec<_>>(),
+            vec![(&http::Method::GET, "/users")]
+        );
+        assert_eq!(
+            router.any_routes().collect::<Vec<_>>(),
+            vec!["/health", "/proxy/*path"]
+        );
+    }
+
+    #[test]
+    fn bad_request() {
+        let mut router: Router<State, HttpError> = Router::new();
+        router.get("/users/:id", |_, mut res: Response, state: State| {
+            let id = state.params().unwrap().get_as::<u64>("id");
+            id.map(|id| res.body(format!("{}", id + 1)).unwrap())
+                .map_err(BadRequest)
+        });
+
+        let mut app = App::new();
+        app.add(router);
+        let app = app.build();
+
+        let req = http::Request::get("http://localhost/users/41")
+            .body(Body::empty())
+            .unwrap();
+        let res = app
+            .execute(req, Response::new(), State::new(), |_, _, _| {
+                let mut res = Response::new();
+                res.status(StatusCode::NOT_FOUND);
+                Ok::<_, HttpError>(res).into_response()
+            })
+            .wait()
+            .unwrap();
+        let body = String::from_utf8(res.into_body().concat2().wait().unwrap().to_vec()).unwrap();
+        assert_eq!(body, "42");
+
+        let err = BadRequest(ParamError::Missing {
+            name: "id".to_string(),
+        });
+        assert_eq!(err.to_string(), "bad request: missing param `id`");
+        match HttpError::from(err) {
+            HttpError::Status(status) => assert_eq!(status, StatusCode::BAD_REQUEST),
+            _ => panic!("expected a status error"),
+        }
+    }
 }
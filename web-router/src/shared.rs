@@ -0,0 +1,64 @@
+use std::mem;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use web::{HttpError, Middleware, Next, Request, Response, ResponseFuture};
+
+use {AsParams, Router};
+
+/// A cloneable handle to a router, whose routes can be replaced at runtime (e.g. when reloading
+/// them from a config) by any of its clones. Each request is resolved against a snapshot of the
+/// router taken when it arrives, so it sees either the old or the new routes, never a mix of both.
+/// Requests in flight when the router is replaced keep using the old one.
+pub struct SharedRouter<S, E: Into<HttpError>>(Arc<RwLock<Arc<Router<S, E>>>>);
+
+impl<S, E> SharedRouter<S, E>
+where
+    E: Into<HttpError>,
+{
+    pub fn new(router: Router<S, E>) -> Self {
+        SharedRouter(Arc::new(RwLock::new(Arc::new(router))))
+    }
+
+    /// Returns the current router.
+    pub fn snapshot(&self) -> Arc<Router<S, E>> {
+        let router = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        router.clone()
+    }
+
+    /// Atomically replaces the router and returns the previous one. The new router should be
+    /// fully built (and ideally frozen) before, as it cannot be modified anymore afterwards.
+    pub fn replace(&self, router: Router<S, E>) -> Arc<Router<S, E>> {
+        let router = Arc::new(router);
+        let mut current = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        mem::replace(&mut *current, router)
+    }
+}
+
+impl<S, E> Clone for SharedRouter<S, E>
+where
+    E: Into<HttpError>,
+{
+    fn clone(&self) -> Self {
+        SharedRouter(self.0.clone())
+    }
+}
+
+impl<S, E> From<Router<S, E>> for SharedRouter<S, E>
+where
+    E: Into<HttpError>,
+{
+    fn from(router: Router<S, E>) -> Self {
+        SharedRouter::new(router)
+    }
+}
+
+impl<S, E> Middleware<S> for SharedRouter<S, E>
+where
+    S: AsParams,
+    E: Into<HttpError> + 'static,
+{
+    fn handle(&self, req: Request, res: Response, state: S, next: Next<S>) -> ResponseFuture {
+        // the lock is only held to take the snapshot, not while the request is handled
+        self.snapshot().handle(req, res, state, next)
+    }
+}